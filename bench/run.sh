@@ -0,0 +1,58 @@
+#!/bin/sh
+
+# Copyright 2022 aiocat
+# 
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+# 
+#     http://www.apache.org/licenses/LICENSE-2.0
+# 
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+
+# time every benchmark with vm, tree-walker and jel of given git revision, which
+# is the first commit by default. usage: bench/run.sh [revision]
+cd "$(dirname "$0")" || exit 1
+revision=${1:-$(git rev-list --max-parents=0 HEAD)}
+cargo build --quiet --release || exit 1
+jel="$(pwd)/../target/release/jel"
+
+# build the old revision from an exported copy, repository is not changed
+base=$(mktemp -d)
+trap 'rm -rf "$base"' EXIT
+mkdir "$base/src"
+git -C .. archive "$revision" | tar -x -C "$base/src" || exit 1
+(cd "$base/src" && cargo build --quiet --release --target-dir "$base/target") || exit 1
+old="$base/target/release/jel"
+
+# print milliseconds of a run, output is saved to check it
+time_run() {
+    start=$(date +%s%N)
+    "$@" > "$base/output.txt" 2>&1
+    end=$(date +%s%N)
+    echo $(((end - start) / 1000000))
+}
+
+failed=0
+printf '%-14s %10s %10s %10s %10s\n' benchmark old tree vm speedup
+
+for bench in *.jel; do
+    old_time=$(time_run "$old" "$bench")
+    old_output=$(cat "$base/output.txt")
+    tree_time=$(time_run "$jel" --tree "$bench")
+    vm_time=$(time_run "$jel" "$bench")
+
+    if [ "$(cat "$base/output.txt")" != "$old_output" ]; then
+        echo "[$bench] output differs from $revision"
+        failed=1
+    fi
+
+    speedup=$(awk "BEGIN { printf \"%.1fx\", $old_time / ($vm_time > 0 ? $vm_time : 1) }")
+    printf '%-14s %8sms %8sms %8sms %10s\n' "$bench" "$old_time" "$tree_time" "$vm_time" "$speedup"
+done
+
+exit $failed
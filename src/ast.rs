@@ -0,0 +1,88 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::parser::Span;
+use std::rc::Rc;
+
+// syntax tree node, parser outputs a list of them. nodes are shared, so
+// copying one (like a function body) doesn't copy the tree
+#[derive(Debug, Clone)]
+pub enum Node {
+    Call(Rc<Call>),           // named command, like "print(x)"
+    Concat(Rc<Concat>),       // braces without a name, like "(x y)"
+    ParamList(Rc<ParamList>), // parameters of "function" and "lambda"
+    Literal(Rc<Literal>),     // words and strings
+}
+
+// node functions
+impl Node {
+    // return where node is written
+    pub fn span(&self) -> &Span {
+        match self {
+            Node::Call(call) => &call.span,
+            Node::Concat(concat) => &concat.span,
+            Node::ParamList(list) => &list.span,
+            Node::Literal(literal) => &literal.span,
+        }
+    }
+}
+
+// literal struct
+#[derive(Debug, Clone, Default)]
+pub struct Literal {
+    pub value: String, // literal text
+    pub quoted: bool,  // true if written in quotes
+    pub span: Span,    // where literal is written
+}
+
+// call struct
+#[derive(Debug, Clone, Default)]
+pub struct Call {
+    pub name: String,         // command name
+    pub arguments: Vec<Node>, // command arguments
+    pub span: Span,           // where command name is written
+    pub end: Span,            // where closing brace is written
+}
+
+// concat struct, values of the arguments are joined
+#[derive(Debug, Clone, Default)]
+pub struct Concat {
+    pub arguments: Vec<Node>, // joined arguments
+    pub span: Span,           // where opening brace is written
+    pub end: Span,            // where closing brace is written
+}
+
+// parameter list struct
+#[derive(Debug, Clone, Default)]
+pub struct ParamList {
+    pub parameters: Vec<Param>, // parameters in written order
+    pub span: Span,             // where opening brace is written
+    pub end: Span,              // where closing brace is written
+}
+
+// parameter enum
+#[derive(Debug, Clone)]
+pub enum Param {
+    Name(Rc<Literal>),          // "name"
+    Default(Rc<Literal>, Node), // "(name value)", value is evaluated on every call
+    Rest(Rc<Literal>),          // "...name", literal keeps the name without dots
+}
+
+// comment struct, comments are not a part of the tree
+#[derive(Debug, Clone, Default)]
+pub struct Comment {
+    pub text: String,   // text after "#"
+    pub own_line: bool, // false if it comes after a command
+    pub span: Span,     // where "#" is written
+}
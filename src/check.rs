@@ -0,0 +1,45 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jel::checker::check_source;
+use jel::debug;
+use std::fs::read_to_string;
+
+// run "jel check", returns exit code
+// files are not run, every problem found in them is printed
+pub fn run(files: &[String]) -> i32 {
+    if files.is_empty() {
+        eprintln!("[JEL]: usage: jel check <files>");
+        return 2;
+    }
+
+    let mut failed = false;
+    for file in files {
+        let source = match read_to_string(file) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("[JEL] at [READING]: can't read file \"{file}\": {err}");
+                failed = true;
+                continue;
+            }
+        };
+
+        for err in check_source(&source, file) {
+            debug::send(&err, Some(&source));
+            failed = true;
+        }
+    }
+
+    i32::from(failed)
+}
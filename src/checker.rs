@@ -0,0 +1,461 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::ast::{Call, Node};
+use crate::commands::builtin::Builtin;
+use crate::commands::function::{check_argument_count, parse_parameters, Parameter};
+use crate::commands::registry::Registry;
+use crate::debug::{ErrorKind, JelError};
+use crate::machine::SPECIAL_FORMS;
+use crate::parser::{Parser, Span};
+use crate::value::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use std::mem::take;
+use std::path::{Path, PathBuf};
+
+// check source code without running it, returns syntax errors if it can't be parsed
+pub fn check_source(source: &str, file: &str) -> Vec<JelError> {
+    let mut parser = Parser::with_file(source, file);
+    if parser.parse().is_err() {
+        return parser.diagnostics;
+    }
+
+    // imports are relative to the file
+    let directory = Path::new(file).parent().unwrap_or(Path::new(""));
+    let mut checker = Checker::new();
+    checker.define(&parser.output, directory, &mut HashSet::new());
+    checker.check(&parser.output)
+}
+
+// what checker knows about variables at a point of the script
+#[derive(Debug, Clone, Default)]
+struct State {
+    consumed: HashMap<String, Span>, // variables that are surely consumed, with the read
+    borrow_reads: bool,              // if true, "get" doesn't consume variables
+    ended: Option<String>,           // command that stopped running, like "return"
+}
+
+// state functions
+impl State {
+    // state after one of two ways is run, only things that are same in both are known
+    fn merge(self, other: State) -> State {
+        if self.ended.is_some() {
+            return other;
+        } else if other.ended.is_some() {
+            return self;
+        }
+
+        State {
+            consumed: self
+                .consumed
+                .into_iter()
+                .filter(|(name, _)| other.consumed.contains_key(name))
+                .collect(),
+            borrow_reads: self.borrow_reads || other.borrow_reads,
+            ended: None,
+        }
+    }
+}
+
+// states of a loop that is being checked
+#[derive(Debug, Default)]
+struct Exits {
+    breaks: Vec<State>,    // states at "break" commands
+    continues: Vec<State>, // states at "continue" commands
+}
+
+// checker struct
+pub struct Checker {
+    registry: Registry,                                 // builtins
+    functions: HashMap<String, Option<Vec<Parameter>>>, // defined functions, with known parameters
+    complete: bool,    // false if some functions can't be found without running
+    loops: Vec<Exits>, // open loops, innermost is the last
+    diagnostics: Vec<JelError>, // found problems
+}
+
+// implement default for checker
+impl Default for Checker {
+    // add default function for default trait
+    fn default() -> Self {
+        Self {
+            registry: Registry::new(),
+            functions: HashMap::new(),
+            complete: true,
+            loops: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
+// checker functions
+impl Checker {
+    // create checker that knows builtins
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // find functions defined in nodes and in files they import
+    pub fn define(&mut self, nodes: &[Node], directory: &Path, visited: &mut HashSet<PathBuf>) {
+        for node in nodes {
+            let (call, arguments) = match node {
+                Node::Call(call) => (Some(call), &call.arguments),
+                Node::Concat(concat) => (None, &concat.arguments),
+                Node::ParamList(_) | Node::Literal(_) => continue,
+            };
+
+            match call.map(|call| (call.name.as_str(), arguments.as_slice())) {
+                Some(("function", [name, parameters, _])) => match literal_name(name) {
+                    // functions defined more than once can have different parameters
+                    Some(name) => {
+                        let parameters = parse_parameters(parameters).ok();
+                        let known = !self.functions.contains_key(&name);
+                        self.functions.insert(name, parameters.filter(|_| known));
+                    }
+                    None => self.complete = false,
+                },
+                Some(("import", [.., path])) => match literal_name(path) {
+                    Some(path) => self.import(&directory.join(path), visited),
+                    None => self.complete = false,
+                },
+                _ => (),
+            }
+
+            self.define(arguments, directory, visited);
+        }
+    }
+
+    // find functions of an imported file
+    fn import(&mut self, path: &Path, visited: &mut HashSet<PathBuf>) {
+        if !visited.insert(path.to_path_buf()) {
+            return;
+        }
+
+        let source = match read_to_string(path) {
+            Ok(source) => source,
+            Err(_) => {
+                self.complete = false;
+                return;
+            }
+        };
+
+        let mut parser = Parser::new(&source);
+        if parser.parse().is_err() {
+            self.complete = false;
+            return;
+        }
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        self.define(&parser.output, directory, visited);
+    }
+
+    // check top-level commands, problems are ordered by position
+    pub fn check(mut self, nodes: &[Node]) -> Vec<JelError> {
+        self.sequence(nodes, &mut State::default());
+
+        // loops are checked twice, keep problems once
+        let mut seen = HashSet::new();
+        let mut diagnostics = take(&mut self.diagnostics);
+        diagnostics.retain(|err| seen.insert(err.to_string()));
+        diagnostics.sort_by_key(|err| err.span.as_ref().map(|span| (span.line, span.column)));
+        diagnostics
+    }
+
+    // save a problem
+    fn report(&mut self, err: JelError, span: &Span) {
+        self.diagnostics.push(err.at(span.clone()));
+    }
+
+    // check commands that run one after another
+    fn sequence(&mut self, nodes: &[Node], state: &mut State) {
+        let running = state.ended.is_none();
+        let mut reported = false;
+
+        for node in nodes {
+            if let (true, false, Some(ended)) = (running, reported, &state.ended) {
+                let message = format!("this is never run, it comes after {ended}.");
+                self.report(JelError::new(ErrorKind::Unreachable, message), node.span());
+                reported = true;
+            }
+
+            self.node(node, state);
+        }
+    }
+
+    // check a node
+    fn node(&mut self, node: &Node, state: &mut State) {
+        match node {
+            Node::Call(call) => self.call(call, state),
+            Node::Concat(concat) => {
+                if !Builtin::Concat.arity().accepts(concat.arguments.len()) {
+                    let err = Builtin::Concat.arity().error("concat");
+                    self.report(err, &concat.span);
+                }
+
+                self.sequence(&concat.arguments, state);
+            }
+            Node::ParamList(_) | Node::Literal(_) => (),
+        }
+    }
+
+    // check a named command
+    fn call(&mut self, call: &Call, state: &mut State) {
+        let arguments = &call.arguments;
+        let span = &call.span;
+
+        // registered commands get evaluated arguments
+        if let Some(found) = self.registry.get(&call.name) {
+            let arity = found.handler.arity();
+            let builtin = found.handler.as_builtin();
+
+            if !arity.accepts(arguments.len()) {
+                let err = arity.error(&found.name);
+                self.report(err, span);
+                self.sequence(arguments, state);
+                return;
+            }
+
+            self.sequence(arguments, state);
+            if let Some(builtin) = builtin {
+                self.builtin(builtin, arguments, span, state);
+            }
+
+            return;
+        }
+
+        // other commands are checked only if they have right argument count
+        if let Some(form) = SPECIAL_FORMS.iter().find(|form| form.name == call.name) {
+            if !form.arity.accepts(arguments.len()) {
+                self.report(form.arity.error(form.name), span);
+                self.sequence(arguments, state);
+                return;
+            }
+        }
+
+        match call.name.as_str() {
+            "do" => self.sequence(arguments, state),
+            "if" => {
+                self.node(&arguments[0], state);
+                let mut otherwise = state.clone();
+                self.node(&arguments[1], state);
+                self.node(&arguments[2], &mut otherwise);
+                *state = take(state).merge(otherwise);
+            }
+            "while" => self.r#loop(Some(&arguments[0]), &[], &arguments[1], state),
+            "for" => {
+                // variable names are evaluated before the iterable
+                let names = match &arguments[1] {
+                    Node::Concat(concat) if concat.arguments.len() == 2 => &concat.arguments[..],
+                    name => std::slice::from_ref(name),
+                };
+
+                self.sequence(names, state);
+                self.node(&arguments[0], state);
+                self.r#loop(None, names, &arguments[2], state);
+            }
+            "try" => {
+                self.node(&arguments[1], state);
+                let entry = state.clone();
+                self.node(&arguments[0], state);
+
+                // body can fail anywhere, only things that are known before and after it are sure
+                let mut handler = entry.merge(state.clone());
+                self.assign(&arguments[1], &mut handler);
+                self.node(&arguments[2], &mut handler);
+                *state = take(state).merge(handler);
+            }
+            "function" => {
+                self.node(&arguments[0], state);
+                self.function(&arguments[1], &arguments[2], state);
+            }
+            "lambda" => self.function(&arguments[0], &arguments[1], state),
+            "call" => {
+                self.node(&arguments[0], state);
+
+                if let Some(name) = literal_name(&arguments[0]) {
+                    self.function_call(&name, arguments.len() - 1, span);
+                }
+
+                self.sequence(&arguments[1..], state);
+            }
+            "import" => {
+                // imported files can define variables again
+                self.sequence(arguments, state);
+                state.consumed.clear();
+            }
+            name => {
+                self.function_call(name, arguments.len(), span);
+                self.sequence(arguments, state);
+            }
+        }
+    }
+
+    // check what a builtin does to variables and control flow
+    fn builtin(&mut self, builtin: Builtin, arguments: &[Node], span: &Span, state: &mut State) {
+        if state.ended.is_some() {
+            return;
+        }
+
+        match builtin {
+            Builtin::Get => self.read(&arguments[0], span, !state.borrow_reads, state),
+            Builtin::Move | Builtin::Drop => self.read(&arguments[0], span, true, state),
+            Builtin::Clone => self.read(&arguments[0], span, false, state),
+            Builtin::Let => self.assign(&arguments[0], state),
+            Builtin::Pragma => match (literal_name(&arguments[0]), literal_name(&arguments[1])) {
+                (Some(name), Some(value)) if name == "reads" => {
+                    state.borrow_reads = value == "borrow";
+                }
+                _ => (),
+            },
+            Builtin::Break | Builtin::Continue => {
+                if let Some(exits) = self.loops.last_mut() {
+                    match builtin {
+                        Builtin::Break => exits.breaks.push(state.clone()),
+                        _ => exits.continues.push(state.clone()),
+                    }
+                }
+
+                state.ended = Some(format!("\"{}\"", builtin.name()));
+            }
+            Builtin::Return | Builtin::Throw => {
+                state.ended = Some(format!("\"{}\"", builtin.name()));
+            }
+            _ => (),
+        }
+    }
+
+    // check a variable read, consumed variables can't be read again
+    fn read(&mut self, name: &Node, span: &Span, consume: bool, state: &mut State) {
+        let name = match literal_name(name) {
+            Some(name) => name,
+            None => return,
+        };
+
+        // a problem is reported once, following reads would only repeat it
+        if let Some(consumed) = state.consumed.remove(&name) {
+            let message = format!(
+                "variable \"{name}\" is already consumed by the read at line {}, use \"clone\" to keep it.",
+                consumed.line
+            );
+            self.report(JelError::new(ErrorKind::UnknownVariable, message), span);
+        } else if consume {
+            state.consumed.insert(name, span.clone());
+        }
+    }
+
+    // variable is defined again
+    fn assign(&mut self, name: &Node, state: &mut State) {
+        if let Some(name) = literal_name(name) {
+            state.consumed.remove(&name);
+        }
+    }
+
+    // check a loop, body is checked twice so reads of the second iteration are known
+    fn r#loop(&mut self, condition: Option<&Node>, names: &[Node], body: &Node, state: &mut State) {
+        self.loops.push(Exits::default());
+
+        let mut exits = Vec::new();
+        for _ in 0..2 {
+            if let Some(condition) = condition {
+                self.node(condition, state);
+            }
+
+            // loop can stop before every iteration, except "while(true ...)"
+            if !condition.is_some_and(is_true) {
+                exits.push(state.clone());
+            }
+
+            for name in names {
+                self.assign(name, state);
+            }
+
+            self.node(body, state);
+
+            // next iteration starts after body or after a "continue"
+            let continues = take(&mut self.loops.last_mut().unwrap().continues);
+            for other in continues {
+                *state = take(state).merge(other);
+            }
+        }
+
+        let breaks = self.loops.pop().unwrap().breaks;
+        let mut after = State {
+            ended: Some("an endless loop".to_string()),
+            ..state.clone()
+        };
+
+        for exit in exits.into_iter().chain(breaks) {
+            after = after.merge(exit);
+        }
+
+        *state = after;
+    }
+
+    // check a function or lambda, body is run later in its own scope
+    fn function(&mut self, parameters: &Node, body: &Node, state: &State) {
+        let mut inner = State {
+            borrow_reads: state.borrow_reads,
+            ..Default::default()
+        };
+
+        match parse_parameters(parameters) {
+            Ok(parameters) => {
+                // default values are evaluated in function scope
+                for parameter in parameters {
+                    if let Parameter::Default(_, value) = parameter {
+                        self.node(&value, &mut inner);
+                    }
+                }
+            }
+            Err(err) => self.report(err, parameters.span()),
+        }
+
+        // loops can't be controlled from a function
+        let loops = take(&mut self.loops);
+        self.node(body, &mut inner);
+        self.loops = loops;
+    }
+
+    // check if called function is defined and gets right argument count
+    fn function_call(&mut self, name: &str, count: usize, span: &Span) {
+        match self.functions.get(name) {
+            Some(Some(parameters)) => {
+                if let Err(err) = check_argument_count(name, parameters, count) {
+                    self.report(err, span);
+                }
+            }
+            Some(None) => (),
+            None if self.complete => {
+                let message = format!("function \"{name}\" is never defined.");
+                self.report(JelError::new(ErrorKind::UnknownFunction, message), span);
+            }
+            None => (),
+        }
+    }
+}
+
+// name written as a literal, same as the name machine gets
+fn literal_name(node: &Node) -> Option<String> {
+    match node {
+        Node::Literal(literal) => {
+            Some(Value::from_literal(&literal.value, literal.quoted).to_string())
+        }
+        _ => None,
+    }
+}
+
+// check if node is a "true" literal
+fn is_true(node: &Node) -> bool {
+    matches!(node, Node::Literal(literal) if !literal.quoted && literal.value == "true")
+}
@@ -0,0 +1,418 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::commands::compare::compare;
+use crate::commands::math::Operator;
+use crate::commands::registry::{Arity, CommandHandler};
+use crate::debug::JelError;
+use crate::machine;
+use crate::value::Value;
+use std::cmp::Ordering;
+
+// built-in commands, they get their arguments evaluated from left to right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Print,
+    Input,
+    Sleep,
+    Concat,
+    Let,
+    Get,
+    Move,
+    Clone,
+    Drop,
+    List,
+    ListPush,
+    ListPop,
+    ListGet,
+    ListSet,
+    ListLen,
+    ListSlice,
+    ListJoin,
+    ListReverse,
+    ListSort,
+    Map,
+    MapGet,
+    MapSet,
+    MapHas,
+    MapRemove,
+    MapKeys,
+    MapValues,
+    MapLen,
+    Pragma,
+    Return,
+    Throw,
+    Dylib,
+    Native,
+    Equals,
+    Not,
+    Bigger,
+    Smaller,
+    Assert,
+    Break,
+    Continue,
+    Float,
+    Int,
+    Bool,
+    Type,
+    Math(Operator),
+    FileRead,
+    FileWrite,
+    FileExists,
+    FileMake,
+    FileRemove,
+    FileAppend,
+    FileType,
+}
+
+// command names of builtins, they are registered to every machine
+pub const BUILTINS: &[(&str, Builtin)] = &[
+    // from commands/process.rs
+    ("print", Builtin::Print),
+    ("input", Builtin::Input),
+    ("sleep", Builtin::Sleep),
+    // from commands/variable.rs
+    ("let", Builtin::Let),
+    ("get", Builtin::Get),
+    ("move", Builtin::Move),
+    ("clone", Builtin::Clone),
+    ("drop", Builtin::Drop),
+    // from commands/list.rs
+    ("list", Builtin::List),
+    ("list.push", Builtin::ListPush),
+    ("list.pop", Builtin::ListPop),
+    ("list.get", Builtin::ListGet),
+    ("list.set", Builtin::ListSet),
+    ("list.len", Builtin::ListLen),
+    ("list.slice", Builtin::ListSlice),
+    ("list.join", Builtin::ListJoin),
+    ("list.reverse", Builtin::ListReverse),
+    ("list.sort", Builtin::ListSort),
+    // from commands/map.rs
+    ("map", Builtin::Map),
+    ("map.get", Builtin::MapGet),
+    ("map.set", Builtin::MapSet),
+    ("map.has", Builtin::MapHas),
+    ("map.remove", Builtin::MapRemove),
+    ("map.keys", Builtin::MapKeys),
+    ("map.values", Builtin::MapValues),
+    ("map.len", Builtin::MapLen),
+    // from commands/pragma.rs
+    ("pragma", Builtin::Pragma),
+    // from commands/function.rs
+    ("return", Builtin::Return),
+    // from commands/error.rs
+    ("throw", Builtin::Throw),
+    // from commands/dylib.rs
+    ("dylib", Builtin::Dylib),
+    ("native", Builtin::Native),
+    // from commands/compare.rs
+    ("equals", Builtin::Equals),
+    ("not", Builtin::Not),
+    ("bigger", Builtin::Bigger),
+    ("smaller", Builtin::Smaller),
+    ("assert", Builtin::Assert),
+    // from commands/iterate.rs
+    ("break", Builtin::Break),
+    ("continue", Builtin::Continue),
+    // from commands/cast.rs
+    ("float", Builtin::Float),
+    ("int", Builtin::Int),
+    ("bool", Builtin::Bool),
+    ("type", Builtin::Type),
+    // from commands/math.rs
+    ("+", Builtin::Math(Operator::Add)),
+    ("-", Builtin::Math(Operator::Sub)),
+    ("*", Builtin::Math(Operator::Mul)),
+    ("/", Builtin::Math(Operator::Div)),
+    ("%", Builtin::Math(Operator::Mod)),
+    // from commands/fs.rs
+    ("file.read", Builtin::FileRead),
+    ("file.write", Builtin::FileWrite),
+    ("file.exists", Builtin::FileExists),
+    ("file.make", Builtin::FileMake),
+    ("file.remove", Builtin::FileRemove),
+    ("file.append", Builtin::FileAppend),
+    ("file.type", Builtin::FileType),
+];
+
+// builtin functions
+impl Builtin {
+    // return command name, used in error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+            Builtin::Input => "input",
+            Builtin::Sleep => "sleep",
+            Builtin::Concat => "concat",
+            Builtin::Let => "let",
+            Builtin::Get => "get",
+            Builtin::Move => "move",
+            Builtin::Clone => "clone",
+            Builtin::Drop => "drop",
+            Builtin::List => "list",
+            Builtin::ListPush => "list.push",
+            Builtin::ListPop => "list.pop",
+            Builtin::ListGet => "list.get",
+            Builtin::ListSet => "list.set",
+            Builtin::ListLen => "list.len",
+            Builtin::ListSlice => "list.slice",
+            Builtin::ListJoin => "list.join",
+            Builtin::ListReverse => "list.reverse",
+            Builtin::ListSort => "list.sort",
+            Builtin::Map => "map",
+            Builtin::MapGet => "map.get",
+            Builtin::MapSet => "map.set",
+            Builtin::MapHas => "map.has",
+            Builtin::MapRemove => "map.remove",
+            Builtin::MapKeys => "map.keys",
+            Builtin::MapValues => "map.values",
+            Builtin::MapLen => "map.len",
+            Builtin::Pragma => "pragma",
+            Builtin::Return => "return",
+            Builtin::Throw => "throw",
+            Builtin::Dylib => "dylib",
+            Builtin::Native => "native",
+            Builtin::Equals => "equals",
+            Builtin::Not => "not",
+            Builtin::Bigger => "bigger",
+            Builtin::Smaller => "smaller",
+            Builtin::Assert => "assert",
+            Builtin::Break => "break",
+            Builtin::Continue => "continue",
+            Builtin::Float => "float",
+            Builtin::Int => "int",
+            Builtin::Bool => "bool",
+            Builtin::Type => "type",
+            Builtin::Math(operator) => operator.name(),
+            Builtin::FileRead => "file.read",
+            Builtin::FileWrite => "file.write",
+            Builtin::FileExists => "file.exists",
+            Builtin::FileMake => "file.make",
+            Builtin::FileRemove => "file.remove",
+            Builtin::FileAppend => "file.append",
+            Builtin::FileType => "file.type",
+        }
+    }
+
+    // return accepted argument count
+    pub fn arity(&self) -> Arity {
+        match self {
+            Builtin::Print | Builtin::List | Builtin::Map => Arity::Any,
+            Builtin::Concat | Builtin::ListPush => Arity::AtLeast(2),
+            Builtin::ListSlice => Arity::Between(2, 3),
+            Builtin::Return => Arity::Between(0, 1),
+            Builtin::Break | Builtin::Continue => Arity::Exact(0),
+            Builtin::Input
+            | Builtin::Sleep
+            | Builtin::Get
+            | Builtin::Move
+            | Builtin::Clone
+            | Builtin::Drop
+            | Builtin::ListPop
+            | Builtin::ListLen
+            | Builtin::ListReverse
+            | Builtin::ListSort
+            | Builtin::MapKeys
+            | Builtin::MapValues
+            | Builtin::MapLen
+            | Builtin::Throw
+            | Builtin::Not
+            | Builtin::Float
+            | Builtin::Int
+            | Builtin::Bool
+            | Builtin::Type
+            | Builtin::FileRead
+            | Builtin::FileExists
+            | Builtin::FileMake
+            | Builtin::FileRemove
+            | Builtin::FileType => Arity::Exact(1),
+            Builtin::Let
+            | Builtin::ListGet
+            | Builtin::ListJoin
+            | Builtin::MapGet
+            | Builtin::MapHas
+            | Builtin::MapRemove
+            | Builtin::Pragma
+            | Builtin::Dylib
+            | Builtin::Equals
+            | Builtin::Bigger
+            | Builtin::Smaller
+            | Builtin::Assert
+            | Builtin::Math(_)
+            | Builtin::FileWrite
+            | Builtin::FileAppend => Arity::Exact(2),
+            Builtin::ListSet | Builtin::MapSet | Builtin::Native => Arity::Exact(3),
+        }
+    }
+
+    // return usage and description, shown by language server
+    pub fn doc(&self) -> (&'static str, &'static str) {
+        match self {
+            Builtin::Print => ("print(values...)", "prints values separated with spaces and returns the printed text."),
+            Builtin::Input => ("input(message)", "prints message and returns the line read from stdin."),
+            Builtin::Sleep => ("sleep(milliseconds)", "waits for given milliseconds."),
+            Builtin::Concat => ("(connector values...)", "joins values with the connector, like (\"\" a b) for \"ab\"."),
+            Builtin::Let => ("let(name value)", "sets a variable in current scope."),
+            Builtin::Get => ("get(name)", "returns a variable and removes it, unless reads are borrowed with pragma(reads borrow)."),
+            Builtin::Move => ("move(name)", "returns a variable and removes it."),
+            Builtin::Clone => ("clone(name)", "returns a copy of a variable, it is not removed."),
+            Builtin::Drop => ("drop(name)", "removes a variable."),
+            Builtin::List => ("list(items...)", "creates a list."),
+            Builtin::ListPush => ("list.push(list items...)", "returns the list with items added to the end."),
+            Builtin::ListPop => ("list.pop(list)", "returns the list without its last item."),
+            Builtin::ListGet => ("list.get(list index)", "returns item at index, negative indexes count from the end."),
+            Builtin::ListSet => ("list.set(list index value)", "returns the list with item at index replaced."),
+            Builtin::ListLen => ("list.len(list)", "returns item count of the list."),
+            Builtin::ListSlice => ("list.slice(list start end?)", "returns items from start to end, end is the length if it is not given."),
+            Builtin::ListJoin => ("list.join(list connector)", "joins items to a string with the connector."),
+            Builtin::ListReverse => ("list.reverse(list)", "returns the list in reverse order."),
+            Builtin::ListSort => ("list.sort(list)", "sorts items as numbers if every item is a number, otherwise as strings."),
+            Builtin::Map => ("map(key value...)", "creates a map from key and value pairs."),
+            Builtin::MapGet => ("map.get(map key)", "returns value of the key."),
+            Builtin::MapSet => ("map.set(map key value)", "returns the map with the key set to value."),
+            Builtin::MapHas => ("map.has(map key)", "returns true if map has the key."),
+            Builtin::MapRemove => ("map.remove(map key)", "returns the map without the key."),
+            Builtin::MapKeys => ("map.keys(map)", "returns keys of the map as a list."),
+            Builtin::MapValues => ("map.values(map)", "returns values of the map as a list."),
+            Builtin::MapLen => ("map.len(map)", "returns pair count of the map."),
+            Builtin::Pragma => ("pragma(name value)", "changes the interpreter, pragma(reads borrow) makes get keep variables and pragma(max_depth n) sets maximum call depth."),
+            Builtin::Return => ("return(value?)", "stops the function and returns the value."),
+            Builtin::Throw => ("throw(message)", "raises an error, it can be caught with try."),
+            Builtin::Dylib => ("dylib(name path)", "loads a dynamic library with given name."),
+            Builtin::Native => ("native(library function argument)", "calls a function of a dynamic library with a string argument and returns the string it gives."),
+            Builtin::Equals => ("equals(first second)", "returns true if values are equal, numbers are compared by value."),
+            Builtin::Not => ("not(value)", "returns true if value is false."),
+            Builtin::Bigger => ("bigger(first second)", "returns true if first value is bigger."),
+            Builtin::Smaller => ("smaller(first second)", "returns true if first value is smaller."),
+            Builtin::Assert => ("assert(first second)", "raises an error if values are not equal."),
+            Builtin::Break => ("break()", "stops the loop."),
+            Builtin::Continue => ("continue()", "skips to next iteration of the loop."),
+            Builtin::Float => ("float(value)", "converts value to a float, returns nil if it can't."),
+            Builtin::Int => ("int(value)", "converts value to an integer, returns nil if it can't."),
+            Builtin::Bool => ("bool(value)", "converts value to a boolean, returns nil if it can't."),
+            Builtin::Type => ("type(value)", "returns type name of the value."),
+            Builtin::Math(Operator::Add) => ("+(first second)", "adds two numbers."),
+            Builtin::Math(Operator::Sub) => ("-(first second)", "subtracts second number from first one."),
+            Builtin::Math(Operator::Mul) => ("*(first second)", "multiplies two numbers."),
+            Builtin::Math(Operator::Div) => ("/(first second)", "divides first number by second one, integers stay integers if there is no remainder."),
+            Builtin::Math(Operator::Mod) => ("%(first second)", "returns remainder of the division."),
+            Builtin::FileRead => ("file.read(path)", "returns content of the file, nil if it can't be read."),
+            Builtin::FileWrite => ("file.write(path text)", "writes text to the file, returns true if it is written."),
+            Builtin::FileExists => ("file.exists(path)", "returns true if the path exists."),
+            Builtin::FileMake => ("file.make(path)", "creates an empty file, returns false if it already exists."),
+            Builtin::FileRemove => ("file.remove(path)", "removes the file, returns true if it is removed."),
+            Builtin::FileAppend => ("file.append(path text)", "adds text to end of the file, returns nil if file doesn't exist."),
+            Builtin::FileType => ("file.type(path)", "returns \"file\" or \"dir\", nil if the path doesn't exist."),
+        }
+    }
+}
+
+// builtins are registered as command handlers
+impl CommandHandler for Builtin {
+    fn arity(&self) -> Arity {
+        Builtin::arity(self)
+    }
+
+    fn run(&self, machine: &machine::Machine, arguments: Vec<Value>) -> Result<Value, JelError> {
+        machine.apply_builtin(*self, arguments)
+    }
+
+    fn as_builtin(&self) -> Option<Builtin> {
+        Some(*self)
+    }
+}
+
+impl machine::Machine {
+    // run a builtin with evaluated arguments, argument count must be checked before
+    pub fn apply_builtin(
+        &self,
+        builtin: Builtin,
+        mut arguments: Vec<Value>,
+    ) -> Result<Value, JelError> {
+        match builtin {
+            Builtin::Print => self.print(arguments),
+            Builtin::Input => self.input(arguments),
+            Builtin::Sleep => self.sleep(arguments),
+            Builtin::Concat => self.concat(arguments),
+            Builtin::Let => self.r#let(arguments),
+            Builtin::Get => self.get(arguments),
+            Builtin::Move => self.r#move(arguments),
+            Builtin::Clone => self.clone(arguments),
+            Builtin::Drop => self.drop(arguments),
+            Builtin::List => self.list(arguments),
+            Builtin::ListPush => self.list_push(arguments),
+            Builtin::ListPop => self.list_pop(arguments),
+            Builtin::ListGet => self.list_get(arguments),
+            Builtin::ListSet => self.list_set(arguments),
+            Builtin::ListLen => self.list_len(arguments),
+            Builtin::ListSlice => self.list_slice(arguments),
+            Builtin::ListJoin => self.list_join(arguments),
+            Builtin::ListReverse => self.list_reverse(arguments),
+            Builtin::ListSort => self.list_sort(arguments),
+            Builtin::Map => self.map(arguments),
+            Builtin::MapGet => self.map_get(arguments),
+            Builtin::MapSet => self.map_set(arguments),
+            Builtin::MapHas => self.map_has(arguments),
+            Builtin::MapRemove => self.map_remove(arguments),
+            Builtin::MapKeys => self.map_keys(arguments),
+            Builtin::MapValues => self.map_values(arguments),
+            Builtin::MapLen => self.map_len(arguments),
+            Builtin::Pragma => self.pragma(arguments),
+            Builtin::Return => self.r#return(arguments),
+            Builtin::Throw => self.throw(arguments),
+            Builtin::Dylib => self.dylib(arguments),
+            Builtin::Native => self.native(arguments),
+            Builtin::Equals => self.equals(arguments),
+            Builtin::Not => self.not(arguments),
+            Builtin::Bigger => self.bigger(arguments),
+            Builtin::Smaller => self.smaller(arguments),
+            Builtin::Assert => self.assert(arguments),
+            Builtin::Break => self.r#break(arguments),
+            Builtin::Continue => self.r#continue(arguments),
+            Builtin::Float => self.float(arguments),
+            Builtin::Int => self.int(arguments),
+            Builtin::Bool => self.bool(arguments),
+            Builtin::Type => self.r#type(arguments),
+            Builtin::Math(operator) => {
+                let second = arguments.pop().unwrap();
+                let first = arguments.pop().unwrap();
+                self.calculate(operator, first, second)
+            }
+            Builtin::FileRead => self.readf(arguments),
+            Builtin::FileWrite => self.writef(arguments),
+            Builtin::FileExists => self.existsf(arguments),
+            Builtin::FileMake => self.makef(arguments),
+            Builtin::FileRemove => self.removef(arguments),
+            Builtin::FileAppend => self.appendf(arguments),
+            Builtin::FileType => self.typef(arguments),
+        }
+    }
+
+    // run a builtin with two values, common ones don't need them collected to a list
+    pub fn apply_binary(
+        &self,
+        builtin: Builtin,
+        first: Value,
+        second: Value,
+    ) -> Result<Value, JelError> {
+        match builtin {
+            Builtin::Math(operator) => self.calculate(operator, first, second),
+            Builtin::Equals => Ok(Value::Bool(first.loose_eq(&second))),
+            Builtin::Bigger => Ok(Value::Bool(compare(&first, &second) == Ordering::Greater)),
+            Builtin::Smaller => Ok(Value::Bool(compare(&first, &second) == Ordering::Less)),
+            _ => self.apply_builtin(builtin, vec![first, second]),
+        }
+    }
+}
@@ -12,134 +12,67 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::debug;
+use crate::debug::JelError;
 use crate::machine;
-use crate::parser;
+use crate::value::Value;
+use num_bigint::BigInt;
+use num_traits::FromPrimitive;
 
 impl machine::Machine {
     // run "float" command
-    pub fn float(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("float", 1);
-        }
-
-        let will_converted = self.token_to_string(callback.pop().unwrap());
-        let will_converted = will_converted.as_str();
+    pub fn float(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let will_converted = arguments.pop().unwrap();
 
         // cast to float
-        match will_converted {
-            "true" => crate::to_token!(1.0),
-            "false" => crate::to_token!(0.0),
-            "nil" => crate::to_token!(0.0),
-            _ => {
-                // try cast to float
-                let try_int = will_converted.parse::<isize>();
-                match try_int {
-                    Ok(value) => crate::to_token!(format!("{value}.0")),
-                    Err(_) => {
-                        // try cast to float
-                        let try_float = will_converted.parse::<f64>();
-                        match try_float {
-                            Ok(value) => crate::to_token!(value),
-                            Err(_) => crate::nil_token!(),
-                        }
-                    }
-                }
-            }
-        }
+        Ok(match will_converted {
+            Value::Bool(value) => Value::Float(if value { 1.0 } else { 0.0 }),
+            Value::Nil => Value::Float(0.0),
+            otherwise => match otherwise.to_float() {
+                Some(value) => Value::Float(value),
+                None => Value::Nil,
+            },
+        })
     }
 
     // run "int" command
-    pub fn int(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("int", 1);
-        }
-
-        let will_converted = self.token_to_string(callback.pop().unwrap());
-        let will_converted = will_converted.as_str();
+    pub fn int(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let will_converted = arguments.pop().unwrap();
 
         // cast to integer
-        match will_converted {
-            "true" => crate::to_token!(1),
-            "false" => crate::to_token!(0),
-            "nil" => crate::to_token!(0),
-            _ => {
-                // try cast to float
-                let try_float = will_converted.parse::<f64>();
-                match try_float {
-                    Ok(value) => crate::to_token!(value as isize),
-                    Err(_) => {
-                        // try cast to int
-                        let try_int = will_converted.parse::<isize>();
-                        match try_int {
-                            Ok(value) => crate::to_token!(value),
-                            Err(_) => crate::nil_token!(),
-                        }
-                    }
-                }
-            }
-        }
+        Ok(match will_converted {
+            Value::Bool(value) => Value::Int(value as i64),
+            Value::Nil => Value::Int(0),
+            otherwise => match otherwise.to_number() {
+                Some(Value::Float(value)) => match BigInt::from_f64(value.trunc()) {
+                    Some(value) => Value::from_bigint(value),
+                    None => Value::Nil,
+                },
+                Some(integer) => integer,
+                None => Value::Nil,
+            },
+        })
     }
 
     // run "bool" command
-    pub fn bool(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("int", 1);
-        }
-
-        let will_converted = self.token_to_string(callback.pop().unwrap());
-        let will_converted = will_converted.as_str();
+    pub fn bool(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let will_converted = arguments.pop().unwrap();
 
-        // cast to integer
-        match will_converted {
-            "true" => crate::to_token!(true),
-            "false" => crate::to_token!(false),
-            "nil" => crate::to_token!(false),
-            _ => {
-                // try cast to float
-                let try_float = will_converted.parse::<f64>();
-                match try_float {
-                    Ok(value) => crate::to_token!(value != 0.0),
-                    Err(_) => {
-                        // try cast to int
-                        let try_int = will_converted.parse::<i64>();
-                        match try_int {
-                            Ok(value) => crate::to_token!(value != 0),
-                            Err(_) => crate::nil_token!(),
-                        }
-                    }
-                }
-            }
-        }
+        // cast to boolean
+        Ok(match will_converted {
+            Value::Bool(value) => Value::Bool(value),
+            Value::Nil => Value::Bool(false),
+            Value::Str(ref text) if text == "true" => Value::Bool(true),
+            Value::Str(ref text) if text == "false" || text == "nil" => Value::Bool(false),
+            otherwise => match otherwise.to_float() {
+                Some(value) => Value::Bool(value != 0.0),
+                None => Value::Nil,
+            },
+        })
     }
 
     // run "type" command
-    pub fn r#type(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("type", 1);
-        }
-
-        let will_converted = self.token_to_string(callback.pop().unwrap());
-        let will_converted = will_converted.as_str();
-
-        // check if boolean
-        match will_converted {
-            "true" | "false" => crate::to_token!("bool"),
-            "nil" => crate::nil_token!(),
-            otherwise => {
-                // try convert to integer, float or string
-                if let Ok(_) = otherwise.parse::<isize>() {
-                    crate::to_token!("int")
-                } else if let Ok(_) = otherwise.parse::<f64>() {
-                    crate::to_token!("float")
-                } else {
-                    crate::to_token!("string")
-                }
-            }
-        }
+    pub fn r#type(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let value = arguments.pop().unwrap();
+        Ok(Value::from(value.type_name()))
     }
 }
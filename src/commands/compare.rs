@@ -12,133 +12,109 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::ast;
 use crate::debug;
+use crate::debug::{ErrorKind, JelError};
 use crate::machine;
-use crate::parser;
+use crate::value::Value;
+use std::cmp::Ordering;
 
 impl machine::Machine {
     // run "if" command
-    pub fn r#if(&self, mut callback: Vec<parser::Token>) -> parser::Token {
+    pub fn r#if(&self, callback: &[ast::Node]) -> Result<Value, JelError> {
         // check argument count
         if callback.len() != 3 {
-            debug::send_argc_message("if", 3);
+            return Err(debug::argc_error("if", 3));
         }
 
         // get arguments
-        let do_if_false = callback.pop().unwrap();
-        let do_if_true = callback.pop().unwrap();
-        let condination = self.token_to_string(callback.pop().unwrap());
+        let condination = self.process(&callback[0])?;
 
-        if crate::is_false!(&condination) {
-            self.process(do_if_false)
+        if condination.is_truthy() {
+            self.process(&callback[1])
         } else {
-            self.process(do_if_true)
+            self.process(&callback[2])
         }
     }
 
     // run "equals" command
-    pub fn equals(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("equals", 2);
-        }
-
+    pub fn equals(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get arguments
-        let second_object = self.token_to_string(callback.pop().unwrap());
-        let first_object = self.token_to_string(callback.pop().unwrap());
+        let second_object = arguments.pop().unwrap();
+        let first_object = arguments.pop().unwrap();
 
         // return if equals
-        crate::to_token!(first_object == second_object)
+        Ok(Value::Bool(first_object.loose_eq(&second_object)))
     }
 
     // run "not" command
-    pub fn not(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("not", 1);
-        }
-
-        // get arguments
-        let got_object = self.token_to_string(callback.pop().unwrap());
-
-        if crate::is_false!(got_object) {
-            crate::true_token!()
-        } else {
-            crate::false_token!()
-        }
+    pub fn not(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let got_object = arguments.pop().unwrap();
+        Ok(Value::Bool(!got_object.is_truthy()))
     }
 
     // run "bigger" command
-    pub fn bigger(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("bigger", 2);
-        }
-
-        // get arguments
-        let second_object = self.token_to_string(callback.pop().unwrap());
-        let first_object = self.token_to_string(callback.pop().unwrap());
-
-        // parse arguments
-        let first_number = first_object.parse::<f64>();
-        let second_number = second_object.parse::<f64>();
-
-        // check if any errors
-        if first_number.is_err() || second_number.is_err() {
-            // compare as object
-            crate::to_token!(first_object.len() > second_object.len())
-        } else {
-            // compare as float
-            let first_number = first_number.unwrap();
-            let second_number = second_number.unwrap();
+    pub fn bigger(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let second_object = arguments.pop().unwrap();
+        let first_object = arguments.pop().unwrap();
 
-            crate::to_token!(first_number > second_number)
-        }
+        Ok(Value::Bool(
+            compare(&first_object, &second_object) == Ordering::Greater,
+        ))
     }
 
     // run "smaller" command
-    pub fn smaller(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("smaller", 2);
-        }
-
-        // get arguments
-        let second_object = self.token_to_string(callback.pop().unwrap());
-        let first_object = self.token_to_string(callback.pop().unwrap());
+    pub fn smaller(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let second_object = arguments.pop().unwrap();
+        let first_object = arguments.pop().unwrap();
 
-        // parse arguments
-        let first_number = first_object.parse::<f64>();
-        let second_number = second_object.parse::<f64>();
-
-        // check if any errors
-        if first_number.is_err() || second_number.is_err() {
-            // compare as object
-            crate::to_token!(first_object.len() < second_object.len())
-        } else {
-            // compare as float
-            let first_number = first_number.unwrap();
-            let second_number = second_number.unwrap();
-
-            crate::to_token!(first_number < second_number)
-        }
+        Ok(Value::Bool(
+            compare(&first_object, &second_object) == Ordering::Less,
+        ))
     }
 
     // run "assert" command
-    pub fn assert(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("assert", 2);
+    pub fn assert(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments
+        let second_object = arguments.pop().unwrap();
+        let first_object = arguments.pop().unwrap();
+
+        if !first_object.loose_eq(&second_object) {
+            return Err(JelError::new(
+                ErrorKind::Assertion,
+                "command assert is failed.",
+            ));
         }
 
-        // get arguments
-        let second_object = self.token_to_string(callback.pop().unwrap());
-        let first_object = self.token_to_string(callback.pop().unwrap());
+        Ok(Value::Nil)
+    }
+}
 
-        if first_object != second_object {
-            debug::send_message("command assert is failed.");
-        }
+// compare two arguments as numbers, or by their length if they are not numbers
+pub fn compare(first_object: &Value, second_object: &Value) -> Ordering {
+    // integers are the common case, skip conversions
+    if let (Value::Int(first), Value::Int(second)) = (first_object, second_object) {
+        return first.cmp(second);
+    }
 
-        crate::nil_token!()
+    match (first_object.to_number(), second_object.to_number()) {
+        // compare integers without losing precision
+        (Some(Value::Int(first)), Some(Value::Int(second))) => first.cmp(&second),
+        (
+            Some(first @ (Value::Int(_) | Value::BigInt(_))),
+            Some(second @ (Value::Int(_) | Value::BigInt(_))),
+        ) => first.to_bigint().cmp(&second.to_bigint()),
+        // compare as float
+        (Some(first), Some(second)) => {
+            let first = first.to_float().unwrap();
+            let second = second.to_float().unwrap();
+
+            first.partial_cmp(&second).unwrap_or(Ordering::Equal)
+        }
+        // compare as object
+        _ => first_object
+            .to_string()
+            .len()
+            .cmp(&second_object.to_string().len()),
     }
 }
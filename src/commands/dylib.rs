@@ -19,9 +19,9 @@ use std::ffi::CString;
 use std::os::raw::c_char;
 use std::rc::Rc;
 
-use crate::debug;
+use crate::debug::{ErrorKind, JelError};
 use crate::machine;
-use crate::parser;
+use crate::value::Value;
 
 // dylib struct
 #[derive(Debug)]
@@ -33,16 +33,18 @@ pub struct DynamicLibraryData {
 // dynamic library data functions
 impl DynamicLibraryData {
     // create new dynamic library data (unsafe code)
-    pub fn new(key: String, path: String) -> Self {
+    pub fn new(key: String, path: String) -> Result<Self, JelError> {
         let library = unsafe { Library::new(path) };
 
-        if library.is_err() {
-            debug::send_message("dynamic library not found, please check your path!");
-        }
-
-        Self {
-            key,
-            value: Rc::new(library.unwrap()),
+        match library {
+            Ok(library) => Ok(Self {
+                key,
+                value: Rc::new(library),
+            }),
+            Err(_) => Err(JelError::new(
+                ErrorKind::Ffi,
+                "dynamic library not found, please check your path!",
+            )),
         }
     }
 
@@ -52,7 +54,7 @@ impl DynamicLibraryData {
     }
 
     // call a native function from library
-    pub fn call(&self, function: String, arg: String) -> String {
+    pub fn call(&self, function: String, arg: String) -> Result<String, JelError> {
         let self_name = &self.key;
         let library = self.get();
         unsafe {
@@ -65,17 +67,22 @@ impl DynamicLibraryData {
             // check if exists
             match native_function {
                 Ok(runnable) => {
-                    let c_string = CString::new(arg).unwrap();
+                    let c_string = match CString::new(arg) {
+                        Ok(c_string) => c_string,
+                        Err(_) => {
+                            return Err(JelError::new(
+                                ErrorKind::Ffi,
+                                "native argument can't contain a nul character.",
+                            ))
+                        }
+                    };
                     let result = runnable(c_string.as_ptr());
 
-                    CStr::from_ptr(result as *const _)
+                    Ok(CStr::from_ptr(result as *const _)
                         .to_string_lossy()
-                        .to_string()
-                }
-                Err(err) => {
-                    debug::send_message(&format!("can't find function \"{function}\" from library \"{self_name}\"\n[INFO] library returned this error: {err}"));
-                    String::new()
+                        .to_string())
                 }
+                Err(err) => Err(JelError::new(ErrorKind::Ffi, format!("can't find function \"{function}\" from library \"{self_name}\"\n[INFO] library returned this error: {err}"))),
             }
         }
     }
@@ -84,69 +91,41 @@ impl DynamicLibraryData {
 // main part of the dynamic library support for jel
 impl machine::Machine {
     // run "dylib" command
-    pub fn dylib(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // give error message if argument count is not matching
-        if callback.len() != 2 {
-            debug::send_argc_message("dylib", 2);
-        }
-
+    pub fn dylib(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get arguments (reversed)
-        let second_arg = callback.pop().unwrap();
-        let first_arg = callback.pop().unwrap();
-
-        // get library name
-        let library_name = self.token_to_string(first_arg);
+        let library_path = arguments.pop().unwrap().to_string();
+        let library_name = arguments.pop().unwrap().to_string();
 
-        // get library path
-        let library_path = self.token_to_string(second_arg);
+        // load library
+        let library = DynamicLibraryData::new(library_name, library_path)?;
 
         // remove clone if exists
-        let mut taken = self.dynamic_libs.take();
-        taken.retain(|var| var.key != library_name);
+        let mut dynamic_libs = self.dynamic_libs.borrow_mut();
+        dynamic_libs.retain(|var| var.key != library.key);
 
         // insert variable
-        taken.push(DynamicLibraryData::new(library_name, library_path));
-        self.dynamic_libs.set(taken);
+        dynamic_libs.push(library);
 
-        parser::Token::String(String::from("nil"))
+        Ok(Value::Nil)
     }
 
     // run "native" command
-    pub fn native(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // give error message if argument count is not matching
-        if callback.len() != 3 {
-            debug::send_argc_message("native", 3);
-        }
-
+    pub fn native(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get arguments (reversed)
-        let third_arg = callback.pop().unwrap();
-        let second_arg = callback.pop().unwrap();
-        let first_arg = callback.pop().unwrap();
-
-        // get library name
-        let library_name = self.token_to_string(first_arg);
-
-        // get function name
-        let function_name = self.token_to_string(second_arg);
-
-        // get function arg
-        let function_arg = self.token_to_string(third_arg);
+        let function_arg = arguments.pop().unwrap().to_string();
+        let function_name = arguments.pop().unwrap().to_string();
+        let library_name = arguments.pop().unwrap().to_string();
 
         // find library by key
-        let taken = self.dynamic_libs.take();
-        let library = taken.iter().find(|var| var.key == library_name);
-
-        let result = match library {
-            Some(lib) => parser::Token::String(lib.call(function_name, function_arg)),
-            None => {
-                debug::send_message(&format!(
-                    "dynamic library \"{library_name}\" doesn't exists. (yet?)"
-                ));
-                parser::Token::String(String::new())
-            }
-        };
-
-        self.dynamic_libs.set(taken);
-        result
+        let dynamic_libs = self.dynamic_libs.borrow();
+        let library = dynamic_libs.iter().find(|var| var.key == library_name);
+
+        match library {
+            Some(lib) => lib.call(function_name, function_arg).map(Value::Str),
+            None => Err(JelError::new(
+                ErrorKind::Ffi,
+                format!("dynamic library \"{library_name}\" doesn't exists. (yet?)"),
+            )),
+        }
     }
 }
@@ -0,0 +1,77 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::ast;
+use crate::debug;
+use crate::debug::{ErrorKind, JelError};
+use crate::machine;
+use crate::value::Value;
+use std::collections::BTreeMap;
+
+// convert an error to a map value, so scripts can inspect it
+pub fn error_to_value(error: &JelError) -> Value {
+    let mut pairs = BTreeMap::new();
+    pairs.insert("kind".to_string(), Value::from(error.kind.to_string()));
+    pairs.insert("message".to_string(), Value::from(error.message.clone()));
+
+    // location is nil if it is not known
+    let (line, column) = match &error.span {
+        Some(span) => (Value::Int(span.line as i64), Value::Int(span.column as i64)),
+        None => (Value::Nil, Value::Nil),
+    };
+
+    pairs.insert("line".to_string(), line);
+    pairs.insert("column".to_string(), column);
+
+    // frames of call stack, outermost first
+    let backtrace = error
+        .backtrace
+        .iter()
+        .map(|frame| Value::from(frame.to_string()))
+        .collect();
+
+    pairs.insert("backtrace".to_string(), Value::List(backtrace));
+    Value::Map(pairs)
+}
+
+impl machine::Machine {
+    // run "try" command
+    pub fn r#try(&self, callback: &[ast::Node]) -> Result<Value, JelError> {
+        // check argument count
+        if callback.len() != 3 {
+            return Err(debug::argc_error("try", 3));
+        }
+
+        // get arguments
+        let (body, handler) = (&callback[0], &callback[2]);
+        let variable_name = self.node_to_string(&callback[1])?;
+
+        // run handler with the error, if body fails
+        match self.process(body) {
+            Ok(value) => Ok(value),
+            // control flow is not an error, let it pass
+            Err(err) if err.kind.is_control_flow() => Err(err),
+            Err(err) => {
+                self.set_variable(variable_name, error_to_value(&err));
+                self.process(handler)
+            }
+        }
+    }
+
+    // run "throw" command
+    pub fn throw(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let message = arguments.pop().unwrap().to_string();
+        Err(JelError::new(ErrorKind::Thrown, message))
+    }
+}
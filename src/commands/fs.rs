@@ -12,134 +12,97 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::debug;
+use crate::debug::JelError;
 use crate::machine;
-use crate::parser;
+use crate::value::Value;
 use std::fs;
 use std::path::Path;
 
 impl machine::Machine {
     // run "file.read" command
-    pub fn readf(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("file.read", 1);
-        }
-
+    pub fn readf(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get first argument
-        let first_arg = self.token_to_string(callback.pop().unwrap());
+        let first_arg = arguments.pop().unwrap().to_string();
 
         // read file
-        let read = fs::read(first_arg);
-
-        match read {
-            Ok(content) => crate::to_token!(String::from_utf8_lossy(&content)),
-            Err(_) => crate::nil_token!(),
-        }
+        Ok(match fs::read(first_arg) {
+            Ok(content) => Value::Str(String::from_utf8_lossy(&content).to_string()),
+            Err(_) => Value::Nil,
+        })
     }
 
     // run "file.write" command
-    pub fn writef(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("file.write", 2);
-        }
-
+    pub fn writef(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get arguments
-        let second_arg = self.token_to_string(callback.pop().unwrap());
-        let first_arg = self.token_to_string(callback.pop().unwrap());
+        let second_arg = arguments.pop().unwrap().to_string();
+        let first_arg = arguments.pop().unwrap().to_string();
 
         // write file
-        crate::to_token!(fs::write(first_arg, second_arg).is_ok())
+        Ok(Value::Bool(fs::write(first_arg, second_arg).is_ok()))
     }
 
     // run "file.append" command
-    pub fn appendf(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("file.append", 2);
-        }
-
+    pub fn appendf(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get arguments
-        let second_arg = self.token_to_string(callback.pop().unwrap());
-        let first_arg = self.token_to_string(callback.pop().unwrap());
+        let second_arg = arguments.pop().unwrap().to_string();
+        let first_arg = arguments.pop().unwrap().to_string();
 
         // read + write file
         let read = fs::read(&first_arg);
 
-        match read {
+        Ok(match read {
             Ok(mut content) => {
                 content.extend(second_arg.as_bytes());
-                crate::to_token!(fs::write(first_arg, content).is_ok())
-            },
-            Err(_) => crate::nil_token!()
-        }
+                Value::Bool(fs::write(first_arg, content).is_ok())
+            }
+            Err(_) => Value::Nil,
+        })
     }
 
     // run "file.make" command
-    pub fn makef(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("file.make", 1);
-        }
-
+    pub fn makef(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get arguments
-        let first_arg = self.token_to_string(callback.pop().unwrap());
+        let first_arg = arguments.pop().unwrap().to_string();
 
         if !Path::new(&first_arg).exists() {
-            crate::to_token!(fs::write(first_arg, "").is_ok())
+            Ok(Value::Bool(fs::write(first_arg, "").is_ok()))
         } else {
-            crate::to_token!(false)
+            Ok(Value::Bool(false))
         }
     }
 
     // run "file.exists" command
-    pub fn existsf(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("file.exists", 1);
-        }
-
+    pub fn existsf(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get arguments
-        let first_arg = self.token_to_string(callback.pop().unwrap());
+        let first_arg = arguments.pop().unwrap().to_string();
 
-        crate::to_token!(Path::new(&first_arg).exists())
+        Ok(Value::Bool(Path::new(&first_arg).exists()))
     }
 
     // run "file.remove" command
-    pub fn removef(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("file.remove", 1);
-        }
-
+    pub fn removef(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get arguments
-        let first_arg = self.token_to_string(callback.pop().unwrap());
+        let first_arg = arguments.pop().unwrap().to_string();
 
         // remove
-        crate::to_token!(fs::remove_file(first_arg).is_ok())
+        Ok(Value::Bool(fs::remove_file(first_arg).is_ok()))
     }
 
     // run "file.type" command
-    pub fn typef(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("file.type", 1);
-        }
-
+    pub fn typef(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // get arguments
-        let first_arg = self.token_to_string(callback.pop().unwrap());
+        let first_arg = arguments.pop().unwrap().to_string();
 
         // check if exists
-        match fs::metadata(first_arg) {
+        Ok(match fs::metadata(first_arg) {
             Ok(metadata) => {
                 if metadata.is_dir() {
-                    crate::to_token!("dir")
+                    Value::from("dir")
                 } else {
-                    crate::to_token!("file")
+                    Value::from("file")
                 }
             }
-            Err(_) => crate::nil_token!()
-        }
+            Err(_) => Value::Nil,
+        })
     }
 }
@@ -12,146 +12,419 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::ast;
+use crate::commands::variable::Scope;
+use crate::compiler;
 use crate::debug;
+use crate::debug::{ErrorKind, JelError};
 use crate::machine;
 use crate::parser;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// function parameter enum
+#[derive(Debug, Clone)]
+pub enum Parameter {
+    Required(String),           // "name"
+    Default(String, ast::Node), // "(name value)", value is evaluated on every call
+    Rest(String),               // "...name", collects extra arguments to a list
+}
+
+// implement display for parameter
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Parameter::Required(name) => write!(f, "{name}"),
+            Parameter::Default(name, _) => write!(f, "({name})"),
+            Parameter::Rest(name) => write!(f, "...{name}"),
+        }
+    }
+}
+
+// function body with its parameters, functions made by same command share it
+#[derive(Debug)]
+pub struct Body {
+    pub parameters: Vec<Parameter>, // function parameters
+    pub value: ast::Node,           // body command
+    pub compiled: RefCell<Option<Rc<compiler::Chunk>>>, // bytecode, compiled on first call
+}
+
+// body functions
+impl Body {
+    // create new body
+    pub fn new(parameters: Vec<Parameter>, value: ast::Node) -> Self {
+        Self {
+            parameters,
+            value,
+            compiled: RefCell::new(None),
+        }
+    }
+}
 
 // function struct
 #[derive(Debug, Clone)]
 pub struct FunctionData {
-    pub arguments: Vec<String>, // function arguments
-    pub value: parser::Token,   // uses rc to share token without memory-cost
+    pub name: String,                     // function name, "lambda" for lambdas
+    pub body: Rc<Body>,                   // body and parameters
+    pub borrow_reads: bool,               // read mode of the script that defined function
+    pub captured: HashMap<String, Value>, // variables captured by lambdas
 }
 
 // function data functions
 impl FunctionData {
     // create new function data
-    pub fn new(value: parser::Token, args: Vec<String>) -> Self {
+    pub fn new(name: String, body: Rc<Body>, borrow_reads: bool) -> Self {
         Self {
-            value,
-            arguments: args,
+            name,
+            body,
+            borrow_reads,
+            captured: HashMap::new(),
         }
     }
-
-    // return function value
-    pub fn get(&self) -> parser::Token {
-        // clone variable reference count
-        self.value.clone()
-    }
 }
 
 // main part of the functions (with do command)
 impl machine::Machine {
     // run "do" command
-    pub fn r#do(&self, callback: Vec<parser::Token>) -> parser::Token {
+    pub fn r#do(&self, callback: &[ast::Node]) -> Result<Value, JelError> {
         // give error message if argument count is not matching
         if callback.is_empty() {
-            debug::send_least_argc_message("do", 1);
+            return Err(debug::least_argc_error("do", 1));
         }
 
         // iterate over commands an run them
-        let mut last_output: parser::Token = crate::nil_token!();
+        let mut last_output = Value::Nil;
         for arg in callback {
-            last_output = self.process(arg);
+            last_output = self.process(arg)?;
         }
 
-        last_output
+        Ok(last_output)
     }
 
     // run "function" command
-    pub fn function(&self, mut callback: Vec<parser::Token>) -> parser::Token {
+    pub fn function(&self, callback: &[ast::Node]) -> Result<Value, JelError> {
         // give error message if argument count is not matching
         if callback.len() != 3 {
-            debug::send_argc_message("function", 3);
+            return Err(debug::argc_error("function", 3));
         }
 
-        // get arguments (reversed)
-        let given_command = callback.pop().unwrap();
-        let arguments_token = callback.pop().unwrap();
-        let first_arg = callback.pop().unwrap();
-
         // get function name
-        let function_name = self.token_to_string(first_arg);
-
-        // get arguments
-        let arguments = if let parser::Token::Command(argument_command) = arguments_token {
-            if argument_command.name == String::new() {
-                let mut args = Vec::new();
-
-                for arg in argument_command.arguments {
-                    if let parser::Token::String(value) = arg {
-                        args.push(value);
-                    }
-                }
-
-                args
-            } else {
-                Vec::new()
-            }
-        } else {
-            Vec::new()
-        };
+        let function_name = self.node_to_string(&callback[0])?;
+        let arguments = parse_parameters(&callback[1])?;
 
-        // insert function
-        let mut taken = self.functions.take();
-        taken.insert(function_name, FunctionData::new(given_command, arguments));
-        self.functions.set(taken);
+        // insert function, body node is shared with the syntax tree
+        let body = Body::new(arguments, callback[2].clone());
+        self.define_function(function_name, Rc::new(body));
 
         // return nil
-        crate::nil_token!()
+        Ok(Value::Nil)
+    }
+
+    // add function with given body, old function with same name is replaced
+    pub fn define_function(&self, name: String, body: Rc<Body>) {
+        let data = FunctionData::new(name.clone(), body, self.borrow_reads.get());
+        self.functions.borrow_mut().insert(name, Rc::new(data));
+    }
+
+    // run "lambda" command
+    pub fn lambda(&self, callback: &[ast::Node]) -> Result<Value, JelError> {
+        // give error message if argument count is not matching
+        if callback.len() != 2 {
+            return Err(debug::argc_error("lambda", 2));
+        }
+
+        let arguments = parse_parameters(&callback[0])?;
+        let body = Body::new(arguments, callback[1].clone());
+        Ok(self.make_lambda(Rc::new(body)))
+    }
+
+    // create lambda with given body, it captures a copy of enclosing scope
+    pub fn make_lambda(&self, body: Rc<Body>) -> Value {
+        let mut data = FunctionData::new("lambda".to_string(), body, self.borrow_reads.get());
+        data.captured = self.variables.borrow().capture();
+
+        Value::Function(Rc::new(data))
     }
 
     // run "call" command
-    pub fn call(&self, mut callback: Vec<parser::Token>) -> parser::Token {
+    pub fn call(&self, callback: &[ast::Node], span: &parser::Span) -> Result<Value, JelError> {
         // give error message if argument count is not matching
         if callback.is_empty() {
-            debug::send_least_argc_message("call", 1);
+            return Err(debug::least_argc_error("call", 1));
         }
 
-        // get arguments (reversed)
-        callback.reverse();
-        let first_arg = callback.pop().unwrap();
+        let callee = self.process(&callback[0])?;
+        self.call_with(callee, &callback[1..], span)
+    }
 
-        // get function name
-        let function_name = self.token_to_string(first_arg);
-
-        // return variable value
-        let taken = self.functions.take();
-        let (variables, result) = match taken.get(&function_name) {
-            Some(data) => {
-                //get function arguments
-                let function_args = data.arguments.clone();
-
-                // check argument count
-                if callback.len() != function_args.len() {
-                    debug::send_message(&format!(
-                        "function \"{}\" excepted {} arguments, got {} argument.",
-                        function_name,
-                        function_args.len(),
-                        callback.len()
-                    ));
-                }
-
-                // return command
-                (function_args, data.get())
+    // call function given by value or by name with argument nodes
+    pub fn call_with(
+        &self,
+        callee: Value,
+        callback: &[ast::Node],
+        span: &parser::Span,
+    ) -> Result<Value, JelError> {
+        let data = self.find_function(callee)?;
+        check_argument_count(&data.name, &data.body.parameters, callback.len())?;
+
+        // evaluate arguments in caller's scope
+        let mut given = Vec::with_capacity(callback.len());
+        for node in callback {
+            given.push(self.process(node)?);
+        }
+
+        self.invoke(&data, given, span)
+    }
+
+    // find function by value or by name
+    pub fn find_function(&self, callee: Value) -> Result<Rc<FunctionData>, JelError> {
+        match callee {
+            Value::Function(data) => Ok(data),
+            name => self.lookup_function(&name.to_string()),
+        }
+    }
+
+    // find function by name
+    pub fn lookup_function(&self, name: &str) -> Result<Rc<FunctionData>, JelError> {
+        let found = self.functions.borrow().get(name).cloned();
+
+        found.ok_or_else(|| {
+            JelError::new(
+                ErrorKind::UnknownFunction,
+                format!("function \"{name}\" doesn't exists. (yet?)"),
+            )
+        })
+    }
+
+    // call function with evaluated arguments, argument count must be checked before
+    pub fn invoke(
+        &self,
+        data: &FunctionData,
+        given: Vec<Value>,
+        span: &parser::Span,
+    ) -> Result<Value, JelError> {
+        let chunk = match self.backend.get() {
+            machine::Backend::Vm => Some(self.compile_function(&data.body)),
+            machine::Backend::Tree => None,
+        };
+
+        // captured variables are visible, but arguments shadow them
+        let mut scope = Scope::new(
+            chunk
+                .as_ref()
+                .map(|chunk| chunk.layout.clone())
+                .unwrap_or_default(),
+        );
+        for (name, value) in &data.captured {
+            scope.assign(name, value.clone());
+        }
+
+        // bind arguments, missing ones get their default values
+        let mut given = given.into_iter();
+        let mut defaults = Vec::new();
+        for parameter in &data.body.parameters {
+            match parameter {
+                Parameter::Required(name) => scope.assign(name, given.next().unwrap()),
+                Parameter::Default(name, default) => match given.next() {
+                    Some(value) => scope.assign(name, value),
+                    None => defaults.push((name, default)),
+                },
+                Parameter::Rest(name) => scope.assign(name, Value::List(given.by_ref().collect())),
             }
-            None => {
-                debug::send_message(&format!(
-                    "function \"{function_name}\" doesn't exists. (yet?)"
-                ));
-                panic!();
+        }
+
+        // call command in a new scope, with read mode of the function
+        self.push_frame(format!("function \"{}\"", data.name), span)?;
+        let old_borrow_reads = self.borrow_reads.replace(data.borrow_reads);
+        self.push_scope(scope);
+        let result = self
+            .grow_stack(|| {
+                self.bind_defaults(defaults).and_then(|_| match chunk {
+                    Some(chunk) => self.run_chunk(&chunk),
+                    None => self.process(&data.body.value),
+                })
+            })
+            .map_err(|err| self.attach_backtrace(err));
+        self.pop_scope();
+        self.borrow_reads.set(old_borrow_reads);
+        self.pop_frame();
+
+        match result {
+            // "return" stops the function with its value
+            Err(err) if err.kind == ErrorKind::Return => Ok(self.return_value.take()),
+            // loops can't be controlled from a function
+            Err(err) => Err(err.escaped()),
+            Ok(value) => Ok(value),
+        }
+    }
+
+    // return compiled function body, its variables with literal names get slots.
+    // it is compiled again if commands are registered after it
+    fn compile_function(&self, body: &Body) -> Rc<compiler::Chunk> {
+        let registry = self.registry.borrow();
+        let mut compiled = body.compiled.borrow_mut();
+
+        match &*compiled {
+            Some(chunk) if chunk.version == registry.version() => chunk.clone(),
+            _ => {
+                let chunk = compiler::compile_body(&body.value, &body.parameters, &registry);
+                compiled.insert(Rc::new(chunk)).clone()
             }
+        }
+    }
+
+    // evaluate default values in function scope, so they can use previous arguments
+    fn bind_defaults(&self, defaults: Vec<(&String, &ast::Node)>) -> Result<(), JelError> {
+        for (name, default) in defaults {
+            let value = self.process(default)?;
+            self.set_variable(name.clone(), value);
+        }
+
+        Ok(())
+    }
+
+    // run "return" command
+    pub fn r#return(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // value is optional
+        let value = arguments.pop().unwrap_or_default();
+
+        // unwind to the function call
+        self.return_value.set(value);
+        Err(JelError::new(
+            ErrorKind::Return,
+            "\"return\" can only be used inside a function.",
+        ))
+    }
+
+    // open a new variable scope
+    pub fn push_scope(&self, scope: Scope) {
+        self.variables.borrow_mut().push(scope);
+    }
+
+    // close innermost variable scope
+    pub fn pop_scope(&self) {
+        self.variables.borrow_mut().pop();
+    }
+
+    // add a frame to call stack, fails if stack is too deep
+    pub fn push_frame(&self, name: String, span: &parser::Span) -> Result<(), JelError> {
+        let mut call_stack = self.call_stack.borrow_mut();
+        let max_depth = self.max_depth.get();
+
+        if call_stack.len() >= max_depth {
+            return Err(JelError::new(
+                ErrorKind::StackOverflow,
+                format!("maximum call depth ({max_depth}) exceeded while calling {name}."),
+            ));
+        }
+
+        call_stack.push(debug::Frame {
+            name,
+            span: Some(span.clone()),
+        });
+        Ok(())
+    }
+
+    // remove innermost frame from call stack
+    pub fn pop_frame(&self) {
+        self.call_stack.borrow_mut().pop();
+    }
+
+    // return a copy of call stack
+    pub fn backtrace(&self) -> Vec<debug::Frame> {
+        self.call_stack.borrow().clone()
+    }
+
+    // save call stack to error, control flow doesn't need it
+    pub fn attach_backtrace(&self, err: JelError) -> JelError {
+        if err.kind.is_control_flow() {
+            err
+        } else {
+            err.or_backtrace(self.backtrace())
+        }
+    }
+}
+
+// check if given argument count matches function parameters
+pub fn check_argument_count(
+    name: &str,
+    parameters: &[Parameter],
+    count: usize,
+) -> Result<(), JelError> {
+    let required = parameters
+        .iter()
+        .filter(|parameter| matches!(parameter, Parameter::Required(_)))
+        .count();
+    let has_rest = matches!(parameters.last(), Some(Parameter::Rest(_)));
+    let positional = parameters.len() - has_rest as usize;
+
+    // required parameters are always before the others
+    if count < required {
+        return Err(JelError::new(
+            ErrorKind::Arity,
+            format!(
+                "function \"{name}\" is missing required argument \"{}\".",
+                parameters[count]
+            ),
+        ));
+    }
+
+    if !has_rest && count > positional {
+        let excepted = if required == positional {
+            positional.to_string()
+        } else {
+            format!("at most {positional}")
+        };
+
+        return Err(JelError::new(
+            ErrorKind::Arity,
+            format!("function \"{name}\" excepted {excepted} arguments, got {count} argument."),
+        ));
+    }
+
+    Ok(())
+}
+
+// read parameters from "(first (second default) ...rest)" list
+pub fn parse_parameters(node: &ast::Node) -> Result<Vec<Parameter>, JelError> {
+    let list = match node {
+        ast::Node::ParamList(list) => list,
+        _ => return Ok(Vec::new()),
+    };
+
+    let mut parameters: Vec<Parameter> = Vec::new();
+    for param in &list.parameters {
+        // nothing can come after rest parameter
+        if let Some(Parameter::Rest(name)) = parameters.last() {
+            return Err(JelError::new(
+                ErrorKind::Syntax,
+                format!("rest parameter \"...{name}\" must be the last parameter."),
+            ));
+        }
+
+        let parameter = match param {
+            ast::Param::Name(name) => Parameter::Required(name.value.clone()),
+            ast::Param::Default(name, value) => {
+                Parameter::Default(name.value.clone(), value.clone())
+            }
+            ast::Param::Rest(name) => Parameter::Rest(name.value.clone()),
         };
-        self.functions.set(taken);
 
-        for argument in variables {
-            self.r#let(vec![
-                parser::Token::String(argument.clone()),
-                callback.pop().unwrap(),
-            ]);
+        // required parameters can't follow optional ones
+        if let (Parameter::Required(name), Some(Parameter::Default(..))) =
+            (&parameter, parameters.last())
+        {
+            return Err(JelError::new(
+                ErrorKind::Syntax,
+                format!("required parameter \"{name}\" can't come after a default parameter."),
+            ));
         }
 
-        // call command
-        self.process(result)
+        parameters.push(parameter);
     }
+
+    Ok(parameters)
 }
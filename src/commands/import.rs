@@ -12,59 +12,81 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::ast;
 use crate::debug;
+use crate::debug::{ErrorKind, JelError};
 use crate::machine;
 use crate::parser;
+use crate::value::Value;
 use std::env;
 use std::fs::read_to_string;
 use std::path::PathBuf;
 
 impl machine::Machine {
     // run "import" command
-    pub fn import(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        if callback.is_empty() {
-            debug::send_argc_message("import", 1);
-        }
+    pub fn import(&self, callback: &[ast::Node], span: &parser::Span) -> Result<Value, JelError> {
+        let first_arg = match callback.last() {
+            Some(node) => self.node_to_string(node)?,
+            None => return Err(debug::argc_error("import", 1)),
+        };
 
-        let first_arg = self.token_to_string(callback.pop().unwrap());
-        let machine = self.load_external_file(&first_arg);
+        // run file with a frame in call stack
+        self.push_frame(format!("import \"{first_arg}\""), span)?;
+        let machine = self
+            .grow_stack(|| self.load_external_file(&first_arg))
+            .map_err(|err| self.attach_backtrace(err));
+        self.pop_frame();
+        let machine = machine?;
 
         // append variables
-        let mut variables = self.variables.take();
-        for (key, value) in machine.variables.take() {
-            variables.insert(key, value);
+        let mut variables = self.variables.borrow_mut();
+        for (key, value) in machine
+            .variables
+            .into_inner()
+            .into_global()
+            .into_variables()
+        {
+            variables.global_mut().insert(key, value);
         }
-        self.variables.set(variables);
 
         // append functions
-        let mut functions = self.functions.take();
-        for (key, value) in machine.functions.take() {
+        let mut functions = self.functions.borrow_mut();
+        for (key, value) in machine.functions.into_inner() {
             functions.insert(key, value);
         }
-        self.functions.set(functions);
 
         // append dynamic libraries
-        let mut dynamic_libs = self.dynamic_libs.take();
-        for dynamic_lib in machine.dynamic_libs.take() {
+        let mut dynamic_libs = self.dynamic_libs.borrow_mut();
+        for dynamic_lib in machine.dynamic_libs.into_inner() {
             // remove old one, if exists
             dynamic_libs.retain(|var| var.key != dynamic_lib.key);
             dynamic_libs.push(dynamic_lib);
         }
-        self.dynamic_libs.set(dynamic_libs);
 
-        parser::Token::String(first_arg)
+        Ok(Value::Str(first_arg))
     }
 
     // import external file and return results
-    fn load_external_file(&self, path: &str) -> machine::Machine {
-        let old_working_dir = env::current_dir().unwrap();
+    fn load_external_file(&self, path: &str) -> Result<machine::Machine, JelError> {
+        let old_working_dir = match env::current_dir() {
+            Ok(dir) => dir,
+            Err(err) => return Err(JelError::new(ErrorKind::Io, err.to_string())),
+        };
 
         // read file
-        let file_data = read_to_string(path).unwrap();
+        let file_data = match read_to_string(path) {
+            Ok(data) => data,
+            Err(err) => {
+                return Err(JelError::new(
+                    ErrorKind::Io,
+                    format!("can't import file \"{path}\": {err}"),
+                ))
+            }
+        };
 
         // run parser
-        let mut parser = parser::Parser::new(&file_data);
-        parser.parse();
+        let mut parser = parser::Parser::with_file(&file_data, path);
+        parser.parse()?;
 
         // set working dir
         let mut new_path = PathBuf::from(path);
@@ -73,11 +95,16 @@ impl machine::Machine {
         let _ = env::set_current_dir(new_path);
 
         // run interpreter
+        // imported file continues the call stack
         let mut machine = machine::Machine::new(parser.output);
-        machine.process_whole();
+        machine.call_stack.replace(self.backtrace());
+        machine.max_depth.set(self.max_depth.get());
+        machine.backend.set(self.backend.get());
+        machine.registry.replace(self.registry.borrow().clone());
+        let result = machine.process_whole();
 
         // re-edit directory
         let _ = env::set_current_dir(old_working_dir);
-        machine
+        result.map(|_| machine)
     }
 }
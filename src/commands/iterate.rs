@@ -12,76 +12,256 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::ast;
+use crate::commands::variable::Scopes;
 use crate::debug;
+use crate::debug::{ErrorKind, JelError};
 use crate::machine;
-use crate::parser;
+use crate::value::Value;
+use std::vec::IntoIter;
+
+// loop variable binding of "for" command
+#[derive(Debug, Clone)]
+pub enum Binding {
+    Single(String),         // "item", gets the item ([key value] for maps)
+    Pair(String, String),   // "(first second)", gets index and item or key and value
+    Slot(usize),            // single name that has a slot in innermost scope
+    SlotPair(usize, usize), // pair of names that have slots in innermost scope
+}
+
+// binding functions
+impl Binding {
+    // read "(first second)" pattern, it is only used for lists and maps. quoted
+    // words are not names, so ("" name) is always evaluated to a name
+    pub fn pair(node: &ast::Node) -> Option<Self> {
+        match node {
+            ast::Node::Concat(concat) => match concat.arguments.as_slice() {
+                [ast::Node::Literal(first), ast::Node::Literal(second)]
+                    if !first.quoted && !second.quoted =>
+                {
+                    Some(Binding::Pair(first.value.clone(), second.value.clone()))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // choose binding for iterated value, pattern of other values is a concat
+    // like before, so its evaluated name is used
+    pub fn select(pattern: Option<&Binding>, name: String, iterable: &Value) -> Self {
+        match (pattern, iterable) {
+            (Some(pattern), Value::List(_) | Value::Map(_)) => pattern.clone(),
+            _ => Binding::Single(name),
+        }
+    }
+
+    // use slots of innermost scope for names that have one, so they are not
+    // looked up on every iteration
+    pub fn resolve(self, variables: &Scopes) -> Self {
+        match &self {
+            Binding::Single(name) => variables.slot_of(name).map_or(self, Binding::Slot),
+            Binding::Pair(first, second) => {
+                match (variables.slot_of(first), variables.slot_of(second)) {
+                    (Some(first), Some(second)) => Binding::SlotPair(first, second),
+                    _ => self,
+                }
+            }
+            _ => self,
+        }
+    }
+
+    // name for numbers and characters, pairs are only selected for lists and maps
+    fn single_name(&self) -> &str {
+        match self {
+            Binding::Single(name) => name,
+            _ => unreachable!("only unresolved single names have a name"),
+        }
+    }
+
+    // set variable of a single binding
+    fn assign(&self, machine: &machine::Machine, value: Value) {
+        match self {
+            Binding::Single(name) => machine.assign_variable(name, value),
+            Binding::Slot(slot) => machine.assign_slot(*slot, value),
+            _ => unreachable!("pairs are only bound for lists and maps"),
+        }
+    }
+}
+
+// iteration state of "for" command
+#[derive(Debug)]
+pub enum Iteration {
+    Pairs(IntoIter<(Value, Value)>, bool), // index and item of a list, or key and value of a map
+    Range(i64, i64, bool),                 // current and end of a number, if variable is set
+    Chars(IntoIter<char>),                 // characters of a string
+}
+
+// iteration functions
+impl Iteration {
+    // start iteration over a list, map, number or characters of a string
+    pub fn new(value: Value, binding: &Binding) -> Self {
+        match value {
+            Value::List(items) => Iteration::Pairs(
+                items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, item)| (Value::Int(index as i64), item))
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+                false,
+            ),
+            Value::Map(map) => Iteration::Pairs(
+                map.into_iter()
+                    .map(|(key, value)| (Value::Str(key), value))
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+                true,
+            ),
+            otherwise => match otherwise.to_number() {
+                // variable is optional for numbers
+                Some(Value::Int(number)) => {
+                    Iteration::Range(0, number, Value::from(binding.single_name()).is_truthy())
+                }
+                _ => Iteration::Chars(
+                    otherwise
+                        .to_string()
+                        .chars()
+                        .collect::<Vec<_>>()
+                        .into_iter(),
+                ),
+            },
+        }
+    }
+
+    // bind next item to variables, returns false if iteration is over
+    pub fn bind_next(&mut self, machine: &machine::Machine, binding: &Binding) -> bool {
+        match self {
+            Iteration::Pairs(pairs, is_map) => {
+                let (key, value) = match pairs.next() {
+                    Some(pair) => pair,
+                    None => return false,
+                };
+
+                match binding {
+                    Binding::Pair(first, second) => {
+                        machine.assign_variable(first, key);
+                        machine.assign_variable(second, value);
+                    }
+                    Binding::SlotPair(first, second) => {
+                        machine.assign_slot(*first, key);
+                        machine.assign_slot(*second, value);
+                    }
+                    // single name gets the item of a list, or [key value] of a map
+                    single => {
+                        let item = if *is_map {
+                            Value::List(vec![key, value])
+                        } else {
+                            value
+                        };
+
+                        single.assign(machine, item);
+                    }
+                }
+            }
+            Iteration::Range(current, end, is_bound) => {
+                if current >= end {
+                    return false;
+                }
+
+                if *is_bound {
+                    binding.assign(machine, Value::Int(*current));
+                }
+
+                *current += 1;
+            }
+            Iteration::Chars(chars) => match chars.next() {
+                Some(character) => binding.assign(machine, Value::Str(character.to_string())),
+                None => return false,
+            },
+        }
+
+        true
+    }
+}
 
 impl machine::Machine {
     // run "for" command
-    pub fn r#for(&self, mut callback: Vec<parser::Token>) -> parser::Token {
+    pub fn r#for(&self, callback: &[ast::Node]) -> Result<Value, JelError> {
         // check argument count
         if callback.len() != 3 {
-            debug::send_argc_message("for", 3);
+            return Err(debug::argc_error("for", 3));
         }
 
-        let do_every_iter = callback.pop().unwrap();
-        let variable_name = self.token_to_string(callback.pop().unwrap());
-        let will_iterated = self.token_to_string(callback.pop().unwrap());
-
-        // check if can be a int
-        let iter_num = will_iterated.parse::<isize>();
-        if let Ok(number) = iter_num {
-            // iterate over number
-            let mut last_output: parser::Token = crate::nil_token!();
-            for n in 0..number {
-                if !crate::is_false!(variable_name) {
-                    self.r#let(vec![
-                        parser::Token::String(variable_name.clone()),
-                        crate::to_token!(n),
-                    ]);
-                }
+        let do_every_iter = &callback[2];
 
-                last_output = self.process(do_every_iter.clone());
-            }
+        // "(first second)" binds pairs, key and value for maps or index and item for lists
+        let pattern = Binding::pair(&callback[1]);
+        let name = self.node_to_string(&callback[1])?;
+        let iterable = self.process(&callback[0])?;
+        let binding = Binding::select(pattern.as_ref(), name, &iterable);
 
-            last_output
-        } else {
-            // iterate over character
-            let mut last_output: parser::Token = crate::nil_token!();
-            for character in will_iterated.chars() {
-                self.r#let(vec![
-                    parser::Token::String(variable_name.clone()),
-                    crate::to_token!(character),
-                ]);
-
-                last_output = self.process(do_every_iter.clone());
+        // iterate over items
+        let mut iteration = Iteration::new(iterable, &binding);
+        let mut last_output = Value::Nil;
+        while iteration.bind_next(self, &binding) {
+            if !self.run_loop_body(do_every_iter, &mut last_output)? {
+                break;
             }
-
-            last_output
         }
+
+        Ok(last_output)
     }
 
     // run "while" command
-    pub fn r#while(&self, mut callback: Vec<parser::Token>) -> parser::Token {
+    pub fn r#while(&self, callback: &[ast::Node]) -> Result<Value, JelError> {
         // check argument count
         if callback.len() != 2 {
-            debug::send_argc_message("while", 2);
+            return Err(debug::argc_error("while", 2));
         }
 
         // get arguments
-        let do_every_iter = callback.pop().unwrap();
-        let checking = callback.pop().unwrap();
+        let (checking, do_every_iter) = (&callback[0], &callback[1]);
 
         // start loop
-        let mut last_output: parser::Token = crate::nil_token!();
+        let mut last_output = Value::Nil;
         loop {
-            if !crate::is_false!(self.token_to_string(checking.clone())) {
-                last_output = self.process(do_every_iter.clone());
-            } else {
+            if !self.process(checking)?.is_truthy()
+                || !self.run_loop_body(do_every_iter, &mut last_output)?
+            {
                 break;
             }
         }
 
-        last_output
+        Ok(last_output)
+    }
+
+    // run "break" command
+    pub fn r#break(&self, _arguments: Vec<Value>) -> Result<Value, JelError> {
+        Err(JelError::new(
+            ErrorKind::Break,
+            "\"break\" can only be used inside a loop.",
+        ))
+    }
+
+    // run "continue" command
+    pub fn r#continue(&self, _arguments: Vec<Value>) -> Result<Value, JelError> {
+        Err(JelError::new(
+            ErrorKind::Continue,
+            "\"continue\" can only be used inside a loop.",
+        ))
+    }
+
+    // run body of a loop, returns false if loop is stopped with "break"
+    fn run_loop_body(&self, body: &ast::Node, last_output: &mut Value) -> Result<bool, JelError> {
+        match self.process(body) {
+            Ok(value) => {
+                *last_output = value;
+                Ok(true)
+            }
+            Err(err) if err.kind == ErrorKind::Break => Ok(false),
+            Err(err) if err.kind == ErrorKind::Continue => Ok(true),
+            Err(err) => Err(err),
+        }
     }
 }
@@ -0,0 +1,202 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::commands::compare::compare;
+use crate::debug::{ErrorKind, JelError};
+use crate::machine;
+use crate::value::Value;
+
+impl machine::Machine {
+    // run "list" command
+    pub fn list(&self, arguments: Vec<Value>) -> Result<Value, JelError> {
+        Ok(Value::List(arguments))
+    }
+
+    // run "list.push" command
+    pub fn list_push(&self, arguments: Vec<Value>) -> Result<Value, JelError> {
+        let mut arguments = arguments.into_iter();
+        let mut items = value_to_list("list.push", arguments.next().unwrap())?;
+
+        // push every other argument
+        items.extend(arguments);
+        Ok(Value::List(items))
+    }
+
+    // run "list.pop" command
+    pub fn list_pop(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // remove last item
+        let mut items = value_to_list("list.pop", arguments.pop().unwrap())?;
+        if items.pop().is_none() {
+            return Err(JelError::new(
+                ErrorKind::Index,
+                "command \"list.pop\" can't pop from an empty list.",
+            ));
+        }
+
+        Ok(Value::List(items))
+    }
+
+    // run "list.get" command
+    pub fn list_get(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments
+        let index = arguments.pop().unwrap();
+        let mut items = value_to_list("list.get", arguments.pop().unwrap())?;
+        let index = value_to_index("list.get", index, items.len())?;
+
+        Ok(items.swap_remove(index))
+    }
+
+    // run "list.set" command
+    pub fn list_set(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments
+        let value = arguments.pop().unwrap();
+        let index = arguments.pop().unwrap();
+        let mut items = value_to_list("list.set", arguments.pop().unwrap())?;
+        let index = value_to_index("list.set", index, items.len())?;
+
+        // replace item
+        items[index] = value;
+        Ok(Value::List(items))
+    }
+
+    // run "list.len" command
+    pub fn list_len(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let items = value_to_list("list.len", arguments.pop().unwrap())?;
+        Ok(Value::Int(items.len() as i64))
+    }
+
+    // run "list.slice" command
+    pub fn list_slice(&self, arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments, end is optional
+        let mut arguments = arguments.into_iter();
+        let items = value_to_list("list.slice", arguments.next().unwrap())?;
+        let start = value_to_bound("list.slice", arguments.next().unwrap(), items.len())?;
+        let end = match arguments.next() {
+            Some(end) => value_to_bound("list.slice", end, items.len())?,
+            None => items.len(),
+        };
+
+        if start > end {
+            return Err(JelError::new(
+                ErrorKind::Index,
+                format!("command \"list.slice\" got start {start} after end {end}."),
+            ));
+        }
+
+        Ok(Value::List(items[start..end].to_vec()))
+    }
+
+    // run "list.join" command
+    pub fn list_join(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments
+        let connector = arguments.pop().unwrap().to_string();
+        let items = value_to_list("list.join", arguments.pop().unwrap())?;
+
+        // join items
+        let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+        Ok(Value::Str(items.join(&connector)))
+    }
+
+    // run "list.reverse" command
+    pub fn list_reverse(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let mut items = value_to_list("list.reverse", arguments.pop().unwrap())?;
+        items.reverse();
+
+        Ok(Value::List(items))
+    }
+
+    // run "list.sort" command
+    pub fn list_sort(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let items = value_to_list("list.sort", arguments.pop().unwrap())?;
+
+        // sort as numbers if every item is a number, otherwise as strings.
+        // nan has no order, so it is not a number here
+        let numbers: Option<Vec<Value>> = items
+            .iter()
+            .map(|item| match item.to_number() {
+                Some(Value::Float(value)) if value.is_nan() => None,
+                number => number,
+            })
+            .collect();
+
+        match numbers {
+            Some(numbers) => {
+                let mut pairs: Vec<(Value, Value)> = items.into_iter().zip(numbers).collect();
+                pairs.sort_by(|first, second| compare(&first.1, &second.1));
+                Ok(Value::List(pairs.into_iter().map(|pair| pair.0).collect()))
+            }
+            None => {
+                let mut items = items;
+                items.sort_by_cached_key(|item| item.to_string());
+                Ok(Value::List(items))
+            }
+        }
+    }
+}
+
+// return items of value, if it is a list
+fn value_to_list(name: &str, value: Value) -> Result<Vec<Value>, JelError> {
+    match value {
+        Value::List(items) => Ok(items),
+        _ => Err(JelError::new(
+            ErrorKind::Type,
+            format!("command \"{name}\" excepts a list."),
+        )),
+    }
+}
+
+// convert value to an index, negative indexes start from the end
+fn value_to_index(name: &str, value: Value, len: usize) -> Result<usize, JelError> {
+    let index = value_to_bound(name, value, len)?;
+
+    if index >= len {
+        return Err(JelError::new(
+            ErrorKind::Index,
+            format!("command \"{name}\" got index {index} for a list with {len} item."),
+        ));
+    }
+
+    Ok(index)
+}
+
+// convert value to a slice bound, which can be equal to length
+fn value_to_bound(name: &str, value: Value, len: usize) -> Result<usize, JelError> {
+    let value = value.to_string();
+
+    let index = match value.parse::<isize>() {
+        Ok(index) => index,
+        Err(_) => {
+            return Err(JelError::new(
+                ErrorKind::Type,
+                format!("command \"{name}\" excepts an integer index, got \"{value}\"."),
+            ))
+        }
+    };
+
+    // count negative indexes from the end
+    let resolved = if index < 0 {
+        len as isize + index
+    } else {
+        index
+    };
+
+    if resolved < 0 || resolved as usize > len {
+        return Err(JelError::new(
+            ErrorKind::Index,
+            format!("command \"{name}\" got index {index} for a list with {len} item."),
+        ));
+    }
+
+    Ok(resolved as usize)
+}
@@ -0,0 +1,116 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::debug::{ErrorKind, JelError};
+use crate::machine;
+use crate::value::Value;
+use std::collections::BTreeMap;
+
+impl machine::Machine {
+    // run "map" command
+    pub fn map(&self, arguments: Vec<Value>) -> Result<Value, JelError> {
+        // check argument count
+        if !arguments.len().is_multiple_of(2) {
+            return Err(JelError::new(
+                ErrorKind::Arity,
+                "command \"map\" takes key and value pairs.",
+            ));
+        }
+
+        // collect every pair
+        let mut pairs = BTreeMap::new();
+        let mut arguments = arguments.into_iter();
+        while let (Some(key), Some(value)) = (arguments.next(), arguments.next()) {
+            pairs.insert(key.to_string(), value);
+        }
+
+        Ok(Value::Map(pairs))
+    }
+
+    // run "map.get" command
+    pub fn map_get(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments
+        let key = arguments.pop().unwrap().to_string();
+        let mut pairs = value_to_map("map.get", arguments.pop().unwrap())?;
+
+        match pairs.remove(&key) {
+            Some(value) => Ok(value),
+            None => Err(JelError::new(
+                ErrorKind::Index,
+                format!("key \"{key}\" doesn't exists in map."),
+            )),
+        }
+    }
+
+    // run "map.set" command
+    pub fn map_set(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments
+        let value = arguments.pop().unwrap();
+        let key = arguments.pop().unwrap().to_string();
+        let mut pairs = value_to_map("map.set", arguments.pop().unwrap())?;
+
+        // insert or replace value
+        pairs.insert(key, value);
+        Ok(Value::Map(pairs))
+    }
+
+    // run "map.has" command
+    pub fn map_has(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments
+        let key = arguments.pop().unwrap().to_string();
+        let pairs = value_to_map("map.has", arguments.pop().unwrap())?;
+
+        Ok(Value::Bool(pairs.contains_key(&key)))
+    }
+
+    // run "map.remove" command
+    pub fn map_remove(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments
+        let key = arguments.pop().unwrap().to_string();
+        let mut pairs = value_to_map("map.remove", arguments.pop().unwrap())?;
+
+        // removing a missing key is not an error
+        pairs.remove(&key);
+        Ok(Value::Map(pairs))
+    }
+
+    // run "map.keys" command
+    pub fn map_keys(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let pairs = value_to_map("map.keys", arguments.pop().unwrap())?;
+        Ok(Value::List(pairs.into_keys().map(Value::Str).collect()))
+    }
+
+    // run "map.values" command
+    pub fn map_values(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let pairs = value_to_map("map.values", arguments.pop().unwrap())?;
+        Ok(Value::List(pairs.into_values().collect()))
+    }
+
+    // run "map.len" command
+    pub fn map_len(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let pairs = value_to_map("map.len", arguments.pop().unwrap())?;
+        Ok(Value::Int(pairs.len() as i64))
+    }
+}
+
+// return pairs of value, if it is a map
+fn value_to_map(name: &str, value: Value) -> Result<BTreeMap<String, Value>, JelError> {
+    match value {
+        Value::Map(pairs) => Ok(pairs),
+        _ => Err(JelError::new(
+            ErrorKind::Type,
+            format!("command \"{name}\" excepts a map."),
+        )),
+    }
+}
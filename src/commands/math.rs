@@ -12,163 +12,135 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::debug;
+use crate::debug::{ErrorKind, JelError};
 use crate::machine;
-use crate::parser;
-
-impl machine::Machine {
-    // run "add" command
-    pub fn add(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("+", 2);
-        }
-
-        // get numbers
-        let first_number = self.token_to_string(callback.pop().unwrap());
-        let second_number = self.token_to_string(callback.pop().unwrap());
-
-        // convert to float
-        let to_float_first = first_number.parse::<f64>();
-        let to_float_second = second_number.parse::<f64>();
-
-        // check err
-        if to_float_first.is_err() || to_float_second.is_err() {
-            debug::send_message("+ command only accepts two number.");
-            return crate::nil_token!();
-        } else {
-            let result = to_float_first.unwrap() + to_float_second.unwrap();
+use crate::value::Value;
+use num_bigint::BigInt;
+use num_traits::Zero;
+
+// arithmetic operator enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
 
-            if result.fract() == 0.0 {
-                crate::to_token!(result as isize)
-            } else {
-                crate::to_token!(result)
-            }
+// operator functions
+impl Operator {
+    // return command name of the operator
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::Mod => "%",
         }
     }
 
-    // run "sub" command
-    pub fn sub(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("-", 2);
-        }
-
-        // get numbers
-        let first_number = self.token_to_string(callback.pop().unwrap());
-        let second_number = self.token_to_string(callback.pop().unwrap());
-
-        // convert to float
-        let to_float_first = first_number.parse::<f64>();
-        let to_float_second = second_number.parse::<f64>();
-
-        // check err
-        if to_float_first.is_err() || to_float_second.is_err() {
-            debug::send_message("- command only accepts two number.");
-            return crate::nil_token!();
-        } else {
-            let result = to_float_first.unwrap() - to_float_second.unwrap();
-
-            if result.fract() == 0.0 {
-                crate::to_token!(result as isize)
-            } else {
-                crate::to_token!(result)
-            }
+    // calculate with integers, returns none if result overflows or isn't an integer
+    fn apply_int(&self, first: i64, second: i64) -> Option<i64> {
+        match self {
+            Operator::Add => first.checked_add(second),
+            Operator::Sub => first.checked_sub(second),
+            Operator::Mul => first.checked_mul(second),
+            Operator::Div if first.checked_rem(second)? != 0 => None,
+            Operator::Div => first.checked_div(second),
+            Operator::Mod => first.checked_rem(second),
         }
     }
 
-    // run "mul" command
-    pub fn mul(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("*", 2);
+    // calculate with big integers, returns none if result isn't an integer
+    fn apply_big(&self, first: BigInt, second: BigInt) -> Option<BigInt> {
+        match self {
+            Operator::Add => Some(first + second),
+            Operator::Sub => Some(first - second),
+            Operator::Mul => Some(first * second),
+            Operator::Div if !(&first % &second).is_zero() => None,
+            Operator::Div => Some(first / second),
+            Operator::Mod => Some(first % second),
         }
+    }
 
-        // get numbers
-        let first_number = self.token_to_string(callback.pop().unwrap());
-        let second_number = self.token_to_string(callback.pop().unwrap());
-
-        // convert to float
-        let to_float_first = first_number.parse::<f64>();
-        let to_float_second = second_number.parse::<f64>();
-
-        // check err
-        if to_float_first.is_err() || to_float_second.is_err() {
-            debug::send_message("* command only accepts two number.");
-            return crate::nil_token!();
-        } else {
-            let result = to_float_first.unwrap() * to_float_second.unwrap();
-
-            if result.fract() == 0.0 {
-                crate::to_token!(result as isize)
-            } else {
-                crate::to_token!(result)
-            }
+    // calculate with floats
+    fn apply_float(&self, first: f64, second: f64) -> f64 {
+        match self {
+            Operator::Add => first + second,
+            Operator::Sub => first - second,
+            Operator::Mul => first * second,
+            Operator::Div => first / second,
+            Operator::Mod => first % second,
         }
     }
+}
 
-    // run "div" command
-    pub fn div(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("/", 2);
+impl machine::Machine {
+    // calculate two numbers with given operator
+    pub fn calculate(
+        &self,
+        operator: Operator,
+        first_number: Value,
+        second_number: Value,
+    ) -> Result<Value, JelError> {
+        // integers are the common case, overflows and zeros are handled below
+        if let (Value::Int(first), Value::Int(second)) = (&first_number, &second_number) {
+            if let Some(result) = operator.apply_int(*first, *second) {
+                return Ok(Value::Int(result));
+            }
         }
 
-        // get numbers
-        let first_number = self.token_to_string(callback.pop().unwrap());
-        let second_number = self.token_to_string(callback.pop().unwrap());
+        let name = operator.name();
 
-        // convert to float
-        let to_float_first = first_number.parse::<f64>();
-        let to_float_second = second_number.parse::<f64>();
-
-        // check err
-        if to_float_first.is_err() || to_float_second.is_err() {
-            debug::send_message("/ command only accepts two number.");
-            crate::nil_token!()
-        } else {
-            // check if zero
-            let second = to_float_second.unwrap();
-            if second == 0.0 {
-                debug::send_message("[command /]: second number can't be zero (0).");
-                return crate::nil_token!();
+        // convert to numbers
+        let (first, second) = match (first_number.to_number(), second_number.to_number()) {
+            (Some(first), Some(second)) => (first, second),
+            _ => {
+                return Err(JelError::new(
+                    ErrorKind::Type,
+                    format!("{name} command only accepts two number."),
+                ))
             }
-
-            let result = to_float_first.unwrap() / second;
-            if result.fract() == 0.0 {
-                crate::to_token!(result as isize)
-            } else {
-                crate::to_token!(result)
-            }
-        }
-    }
-    // run "mod" command
-    pub fn r#mod(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 2 {
-            debug::send_argc_message("%", 2);
+        };
+
+        // check if zero
+        if matches!(operator, Operator::Div | Operator::Mod) && second.to_float() == Some(0.0) {
+            return Err(JelError::new(
+                ErrorKind::Arithmetic,
+                format!("[command {name}]: second number can't be zero (0)."),
+            ));
         }
 
-        // get numbers
-        let first_number = self.token_to_string(callback.pop().unwrap());
-        let second_number = self.token_to_string(callback.pop().unwrap());
-
-        // convert to float
-        let to_float_first = first_number.parse::<f64>();
-        let to_float_second = second_number.parse::<f64>();
-
-        // check err
-        if to_float_first.is_err() || to_float_second.is_err() {
-            debug::send_message("% command only accepts two number.");
-            return crate::nil_token!();
-        } else {
-            let result = to_float_first.unwrap() % to_float_second.unwrap();
-
-            if result.fract() == 0.0 {
-                crate::to_token!(result as isize)
+        let result = match (&first, &second) {
+            // calculate as integer, big integers are used if it overflows
+            (Value::Int(first), Value::Int(second)) => operator
+                .apply_int(*first, *second)
+                .map(Value::Int)
+                .or_else(|| {
+                    operator
+                        .apply_big(BigInt::from(*first), BigInt::from(*second))
+                        .map(Value::from_bigint)
+                }),
+            // calculate as big integer
+            (Value::Int(_) | Value::BigInt(_), Value::Int(_) | Value::BigInt(_)) => operator
+                .apply_big(first.to_bigint().unwrap(), second.to_bigint().unwrap())
+                .map(Value::from_bigint),
+            _ => None,
+        };
+
+        // calculate as float if any of them is float, or division has a remainder.
+        // whole results are integers, like "+(1.5 1.5)"
+        Ok(result.unwrap_or_else(|| {
+            let result =
+                operator.apply_float(first.to_float().unwrap(), second.to_float().unwrap());
+
+            if result.fract() == 0.0 && result.abs() < i64::MAX as f64 {
+                Value::Int(result as i64)
             } else {
-                crate::to_token!(result)
+                Value::Float(result)
             }
-        }
+        }))
     }
 }
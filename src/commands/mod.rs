@@ -12,13 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod builtin;
 pub mod cast;
 pub mod compare;
 pub mod dylib;
+pub mod error;
 pub mod fs;
 pub mod function;
 pub mod import;
 pub mod iterate;
+pub mod list;
+pub mod map;
 pub mod math;
+pub mod pragma;
 pub mod process;
+pub mod registry;
 pub mod variable;
@@ -0,0 +1,59 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::debug::{ErrorKind, JelError};
+use crate::machine;
+use crate::value::Value;
+
+impl machine::Machine {
+    // run "pragma" command
+    pub fn pragma(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments
+        let value = arguments.pop().unwrap().to_string();
+        let name = arguments.pop().unwrap().to_string();
+
+        match (name.as_str(), value.as_str()) {
+            // "get" consumes variables (default)
+            ("reads", "move") => self.borrow_reads.set(false),
+            // "get" keeps variables, only "move" consumes
+            ("reads", "borrow") => self.borrow_reads.set(true),
+            // maximum depth of function calls
+            ("max_depth", _) => match value.parse::<usize>() {
+                Ok(depth) if depth > 0 => self.max_depth.set(depth),
+                _ => {
+                    return Err(JelError::new(
+                        ErrorKind::Type,
+                        format!(
+                            "pragma \"max_depth\" excepts a positive integer, got \"{value}\"."
+                        ),
+                    ))
+                }
+            },
+            ("reads", _) => {
+                return Err(JelError::new(
+                    ErrorKind::Type,
+                    format!("pragma \"reads\" excepts \"move\" or \"borrow\", got \"{value}\"."),
+                ))
+            }
+            _ => {
+                return Err(JelError::new(
+                    ErrorKind::Type,
+                    format!("unknown pragma \"{name}\"."),
+                ))
+            }
+        }
+
+        Ok(Value::Nil)
+    }
+}
@@ -12,55 +12,49 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::debug;
+use crate::debug::{ErrorKind, JelError};
 use crate::machine;
-use crate::parser;
+use crate::value::Value;
 use std::io::{stdin, stdout, Write};
 use std::thread::sleep;
 use std::time::Duration;
 
 impl machine::Machine {
     // run "print" command
-    pub fn print(&self, callback: Vec<parser::Token>) -> parser::Token {
-        let mut arguments: Vec<String> = Vec::new();
-
-        // iterate over given arguments
-        for arg in callback {
-            // check token type
-            if let parser::Token::String(value) = arg {
-                // push string
-                arguments.push(value);
-            } else if let parser::Token::Command(_) = arg {
-                // run command and push string
-                if let parser::Token::String(value) = self.process(arg) {
-                    // push string
-                    arguments.push(value);
-                }
-            }
-        }
+    pub fn print(&self, arguments: Vec<Value>) -> Result<Value, JelError> {
+        let arguments: Vec<String> = arguments.iter().map(Value::to_string).collect();
 
         // print collected objects
         let calculated_string = arguments.join(" ");
         println!("{calculated_string}");
 
-        parser::Token::String(calculated_string)
+        Ok(Value::Str(calculated_string))
     }
 
-    // run "input" command
-    pub fn input(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        if callback.len() != 1 {
-            debug::send_argc_message("input", 1);
-        }
+    // run "concat" command, first argument is the connector
+    pub fn concat(&self, arguments: Vec<Value>) -> Result<Value, JelError> {
+        let mut arguments = arguments.iter().map(Value::to_string);
+        let connector = arguments.next().unwrap();
+
+        // return object
+        let calculated_string = arguments.collect::<Vec<String>>().join(&connector);
+        Ok(Value::Str(calculated_string))
+    }
 
+    // run "input" command
+    pub fn input(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
         // print message
-        print!("{}", self.token_to_string(callback.pop().unwrap()));
+        print!("{}", arguments.pop().unwrap());
 
         // get input
         let mut input = String::new();
         let _ = stdout().flush();
-        stdin()
-            .read_line(&mut input)
-            .expect("stdio exception in structware.");
+        if let Err(err) = stdin().read_line(&mut input) {
+            return Err(JelError::new(
+                ErrorKind::Io,
+                format!("can't read from stdin: {err}"),
+            ));
+        }
 
         if let Some('\n') = input.chars().next_back() {
             input.pop();
@@ -70,24 +64,19 @@ impl machine::Machine {
             input.pop();
         }
 
-        crate::to_token!(input)
+        Ok(Value::Str(input))
     }
 
     // run "sleep" command
-    pub fn sleep(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // check argument count
-        if callback.len() != 1 {
-            debug::send_argc_message("sleep", 1);
-        }
-
-        let first_arg = self.token_to_string(callback.pop().unwrap());
+    pub fn sleep(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        let first_arg = arguments.pop().unwrap().to_string();
 
         // sleep
         match first_arg.parse::<u64>() {
             Ok(time) => sleep(Duration::from_millis(time)),
-            Err(_) => return crate::nil_token!(),
+            Err(_) => return Ok(Value::Nil),
         }
 
-        crate::nil_token!()
+        Ok(Value::Nil)
     }
 }
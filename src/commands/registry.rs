@@ -0,0 +1,202 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::ast;
+use crate::commands::builtin::{Builtin, BUILTINS};
+use crate::debug;
+use crate::debug::{ErrorKind, JelError};
+use crate::machine;
+use crate::value::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// argument count of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exact(usize),          // exactly n arguments
+    AtLeast(usize),        // n or more arguments
+    Between(usize, usize), // from first to second arguments
+    Any,                   // any count of arguments
+}
+
+// arity functions
+impl Arity {
+    // check if given argument count is accepted
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(exact) => count == *exact,
+            Arity::AtLeast(least) => count >= *least,
+            Arity::Between(least, most) => count >= *least && count <= *most,
+            Arity::Any => true,
+        }
+    }
+
+    // create argument count error for given command
+    pub fn error(&self, name: &str) -> JelError {
+        match self {
+            Arity::Exact(exact) => debug::argc_error(name, *exact),
+            Arity::AtLeast(least) => debug::least_argc_error(name, *least),
+            Arity::Between(least, most) => JelError::new(
+                ErrorKind::Arity,
+                format!("command \"{name}\" takes {least} or {most} argument."),
+            ),
+            Arity::Any => JelError::new(
+                ErrorKind::Arity,
+                format!("command \"{name}\" got wrong arguments."),
+            ),
+        }
+    }
+}
+
+// command that gets its arguments evaluated from left to right
+pub trait CommandHandler {
+    // accepted argument count, it is checked before arguments are evaluated
+    fn arity(&self) -> Arity;
+
+    // run command with evaluated arguments
+    fn run(&self, machine: &machine::Machine, arguments: Vec<Value>) -> Result<Value, JelError>;
+
+    // builtin run by this handler, compiler uses it to emit specialized instructions
+    fn as_builtin(&self) -> Option<Builtin> {
+        None
+    }
+}
+
+// command handler made from a closure, for commands of the host program
+struct HostCommand<F> {
+    arity: Arity, // accepted argument count
+    function: F,  // command body
+}
+
+// implement command handler for host commands
+impl<F> CommandHandler for HostCommand<F>
+where
+    F: Fn(&machine::Machine, Vec<Value>) -> Result<Value, JelError>,
+{
+    fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    fn run(&self, machine: &machine::Machine, arguments: Vec<Value>) -> Result<Value, JelError> {
+        (self.function)(machine, arguments)
+    }
+}
+
+// registered command
+#[derive(Clone)]
+pub struct CommandEntry {
+    pub name: Rc<str>,                   // name used in error messages
+    pub handler: Rc<dyn CommandHandler>, // runs the command
+}
+
+// implement debug for command entry, handlers can't be printed
+impl fmt::Debug for CommandEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CommandEntry({:?})", self.name)
+    }
+}
+
+// commands by name, default is an empty registry. use "Registry::new" for builtins
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    commands: HashMap<String, CommandEntry>, // commands by name
+    version: usize,                          // changes when a command is added
+}
+
+// registry functions
+impl Registry {
+    // create registry with builtins
+    pub fn new() -> Self {
+        let mut registry = Self::default();
+
+        for (name, builtin) in BUILTINS {
+            registry.commands.insert(
+                name.to_string(),
+                CommandEntry {
+                    name: Rc::from(builtin.name()),
+                    handler: Rc::new(*builtin),
+                },
+            );
+        }
+
+        registry
+    }
+
+    // add command, replaces the old one with same name
+    pub fn insert(&mut self, name: &str, handler: Rc<dyn CommandHandler>) {
+        self.commands.insert(
+            name.to_string(),
+            CommandEntry {
+                name: Rc::from(name),
+                handler,
+            },
+        );
+        self.version += 1;
+    }
+
+    // return version of the commands, code compiled with another version is outdated
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    // find command by name
+    pub fn get(&self, name: &str) -> Option<&CommandEntry> {
+        self.commands.get(name)
+    }
+}
+
+impl machine::Machine {
+    // register a command of the host program, it is used instead of
+    // builtins and functions with same name. function bodies compiled
+    // before are compiled again on their next call
+    pub fn register<F>(&self, name: &str, arity: Arity, function: F)
+    where
+        F: Fn(&machine::Machine, Vec<Value>) -> Result<Value, JelError> + 'static,
+    {
+        self.register_handler(name, Rc::new(HostCommand { arity, function }));
+    }
+
+    // register a command handler
+    pub fn register_handler(&self, name: &str, handler: Rc<dyn CommandHandler>) {
+        self.registry.borrow_mut().insert(name, handler);
+    }
+
+    // find registered command by name
+    pub fn find_command(&self, name: &str) -> Option<CommandEntry> {
+        self.registry.borrow().get(name).cloned()
+    }
+
+    // run a command with argument nodes, they are evaluated from left to right
+    pub fn run_command(
+        &self,
+        name: &str,
+        handler: &dyn CommandHandler,
+        callback: &[ast::Node],
+    ) -> Result<Value, JelError> {
+        // check argument count
+        let arity = handler.arity();
+        if !arity.accepts(callback.len()) {
+            return Err(arity.error(name));
+        }
+
+        // evaluate arguments
+        let mut arguments = Vec::with_capacity(callback.len());
+        for arg in callback {
+            arguments.push(self.process(arg)?);
+        }
+
+        handler.run(self, arguments)
+    }
+}
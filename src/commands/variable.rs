@@ -12,150 +12,394 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::mem::take;
+use std::rc::Rc;
 
-use crate::debug;
+use crate::debug::{ErrorKind, JelError};
 use crate::machine;
-use crate::parser;
+use crate::value::Value;
 
 // variable struct
 #[derive(Debug)]
 pub struct VariableData {
-    pub value: String, // variable value
+    pub value: Value, // variable value
 }
 
 // variable functions
 impl VariableData {
     // create new variable data
-    pub fn new(value: String) -> Self {
+    pub fn new(value: Value) -> Self {
         Self { value }
     }
 
     // take variable
-    pub fn take(&mut self) -> String {
+    pub fn take(&mut self) -> Value {
         take(&mut self.value)
     }
 }
 
-// main part of the command(s)
-impl machine::Machine {
-    // run "let" command
-    pub fn r#let(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // give error message if argument count is not matching
-        if callback.len() != 2 {
-            debug::send_argc_message("let", 2);
+// slots of a compiled function body or of global scope, variables with literal
+// names are found by index
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub names: Vec<String>,            // name of every slot
+    pub slots: HashMap<String, usize>, // slot of every name
+}
+
+// layout functions
+impl Layout {
+    // return slot of the name, it is added if needed
+    pub fn slot(&mut self, name: &str) -> usize {
+        if let Some(slot) = self.slots.get(name) {
+            return *slot;
         }
 
-        // get arguments (reversed)
-        let second_arg = callback.pop().unwrap();
-        let first_arg = callback.pop().unwrap();
+        self.names.push(name.to_string());
+        self.slots.insert(name.to_string(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+}
 
-        // get variable name
-        let variable_name = self.token_to_string(first_arg);
+// scope struct, variables of the body that runs in it are kept in slots
+#[derive(Debug, Default)]
+pub struct Scope {
+    layout: Rc<Layout>,                    // slots of the running body
+    slots: Vec<Option<Value>>,             // variables that have a slot
+    others: HashMap<String, VariableData>, // variables with computed names
+}
 
-        // get variable value
-        let variable_value = self.token_to_string(second_arg);
+// scope functions
+impl Scope {
+    // create empty scope for a body with given slots
+    pub fn new(layout: Rc<Layout>) -> Self {
+        Self {
+            slots: vec![None; layout.names.len()],
+            layout,
+            others: HashMap::new(),
+        }
+    }
 
-        // insert variable
-        let mut taken = self.variables.take();
-        taken.insert(variable_name, VariableData::new(variable_value));
-        self.variables.set(taken);
+    // change layout of the scope, new slots are empty
+    pub fn extend_layout<R>(&mut self, function: impl FnOnce(&mut Layout) -> R) -> R {
+        let result = function(Rc::make_mut(&mut self.layout));
+        self.slots.resize(self.layout.names.len(), None);
+        result
+    }
+
+    // insert variable, existing variable is replaced
+    pub fn insert(&mut self, name: String, value: Value) {
+        match self.layout.slots.get(&name) {
+            Some(slot) => self.slots[*slot] = Some(value),
+            None => {
+                self.others.insert(name, VariableData::new(value));
+            }
+        }
+    }
+
+    // same as insert, but name is only copied for new variables
+    pub fn assign(&mut self, name: &str, value: Value) {
+        if let Some(slot) = self.layout.slots.get(name) {
+            self.slots[*slot] = Some(value);
+            return;
+        }
 
-        crate::nil_token!()
+        match self.others.get_mut(name) {
+            Some(data) => data.value = value,
+            None => {
+                self.others
+                    .insert(name.to_string(), VariableData::new(value));
+            }
+        }
     }
 
-    // run "get" command
-    pub fn get(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // give error message if argument count is not matching
-        if callback.len() != 1 {
-            debug::send_argc_message("get", 1);
+    // find variable by name
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self.layout.slots.get(name) {
+            Some(slot) => self.slots[*slot].as_ref(),
+            None => self.others.get(name).map(|data| &data.value),
         }
+    }
 
-        // get argument
-        let first_arg = callback.pop().unwrap();
+    // remove variable by name
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        match self.layout.slots.get(name) {
+            Some(slot) => self.slots[*slot].take(),
+            None => self.others.remove(name).map(|mut data| data.take()),
+        }
+    }
+
+    // copy every variable
+    pub fn to_map(&self) -> HashMap<String, Value> {
+        let slots = self
+            .layout
+            .names
+            .iter()
+            .zip(&self.slots)
+            .filter_map(|(name, value)| Some((name.clone(), value.clone()?)));
+
+        self.others
+            .iter()
+            .map(|(name, data)| (name.clone(), data.value.clone()))
+            .chain(slots)
+            .collect()
+    }
 
-        // get variable name
-        let variable_name = self.token_to_string(first_arg);
+    // take every variable out of the scope
+    pub fn into_variables(self) -> impl Iterator<Item = (String, Value)> {
+        let slots = self
+            .layout
+            .names
+            .clone()
+            .into_iter()
+            .zip(self.slots)
+            .filter_map(|(name, value)| Some((name, value?)));
+
+        self.others
+            .into_iter()
+            .map(|(name, mut data)| (name, data.take()))
+            .chain(slots)
+    }
+}
+
+// scope stack struct, first scope is the global scope
+#[derive(Debug)]
+pub struct Scopes {
+    stack: Vec<Scope>, // scopes, innermost is the last
+}
+
+// implement default for scopes
+impl Default for Scopes {
+    // add default function for default trait
+    fn default() -> Self {
+        Self {
+            stack: vec![Scope::default()],
+        }
+    }
+}
+
+// scope functions
+impl Scopes {
+    // open a new scope (for function calls)
+    pub fn push(&mut self, scope: Scope) {
+        self.stack.push(scope);
+    }
+
+    // close innermost scope, global scope is never closed
+    pub fn pop(&mut self) -> Option<Scope> {
+        if self.stack.len() > 1 {
+            self.stack.pop()
+        } else {
+            None
+        }
+    }
+
+    // return count of open scopes, it is 1 if only global scope is open
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    // return global scope
+    pub fn global_mut(&mut self) -> &mut Scope {
+        &mut self.stack[0]
+    }
+
+    // take global scope out of the stack
+    pub fn into_global(mut self) -> Scope {
+        self.stack.swap_remove(0)
+    }
+
+    // return innermost scope
+    fn innermost(&self) -> &Scope {
+        self.stack.last().unwrap()
+    }
+
+    // return innermost scope as mutable
+    fn innermost_mut(&mut self) -> &mut Scope {
+        self.stack.last_mut().unwrap()
+    }
+
+    // copy variables of innermost scope, global scope is never copied
+    pub fn capture(&self) -> HashMap<String, Value> {
+        if self.stack.len() == 1 {
+            return HashMap::new();
+        }
+
+        self.innermost().to_map()
+    }
 
-        // dbg!(&self.variables);
-        // find variable by key
-        let mut taken = self.variables.take();
-        let will_return = match taken.get_mut(&variable_name) {
-            Some(data) => data.take(),
+    // insert variable to innermost scope
+    pub fn insert(&mut self, name: String, value: Value) {
+        self.innermost_mut().insert(name, value);
+    }
+
+    // set variable in innermost scope, existing variable is updated in place
+    pub fn assign(&mut self, name: &str, value: Value) {
+        self.innermost_mut().assign(name, value);
+    }
+
+    // find variable in innermost scope, fall back to global scope. scopes between
+    // them belong to callers, so their variables are not visible (no dynamic scoping)
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.innermost()
+            .get(name)
+            .or_else(|| self.stack[0].get(name))
+    }
+
+    // remove variable from innermost scope, fall back to global scope like "get"
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.innermost_mut()
+            .remove(name)
+            .or_else(|| self.stack[0].remove(name))
+    }
+
+    // find slot of the name in innermost scope
+    pub fn slot_of(&self, name: &str) -> Option<usize> {
+        self.innermost().layout.slots.get(name).copied()
+    }
+
+    // name of a slot of innermost scope
+    pub fn slot_name(&self, slot: usize) -> &str {
+        &self.innermost().layout.names[slot]
+    }
+
+    // find variable by slot of innermost scope, fall back to global scope by name
+    pub fn get_slot(&self, slot: usize) -> Option<&Value> {
+        let innermost = self.innermost();
+
+        match &innermost.slots[slot] {
+            Some(value) => Some(value),
+            None => self.stack[0].get(&innermost.layout.names[slot]),
+        }
+    }
+
+    // remove variable by slot of innermost scope, fall back to global scope by name
+    pub fn remove_slot(&mut self, slot: usize) -> Option<Value> {
+        let innermost = self.stack.last_mut().unwrap();
+
+        match innermost.slots[slot].take() {
+            Some(value) => Some(value),
             None => {
-                debug::send_message(&format!(
-                    "variable \"{variable_name}\" doesn't exists. (yet?)"
-                ));
-                String::new()
+                let name = innermost.layout.names[slot].clone();
+                self.stack[0].remove(&name)
             }
-        };
+        }
+    }
 
-        // remove variable
-        taken.remove(&variable_name).unwrap();
+    // set variable by slot of innermost scope
+    pub fn assign_slot(&mut self, slot: usize, value: Value) {
+        self.innermost_mut().slots[slot] = Some(value);
+    }
+}
 
-        // return variable
-        self.variables.set(taken);
-        parser::Token::String(will_return)
+// main part of the command(s)
+impl machine::Machine {
+    // run "let" command
+    pub fn r#let(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // get arguments (reversed)
+        let variable_value = arguments.pop().unwrap();
+        let variable_name = arguments.pop().unwrap().to_string();
+
+        // insert variable
+        self.set_variable(variable_name, variable_value);
+        Ok(Value::Nil)
+    }
+
+    // insert variable to innermost scope
+    pub fn set_variable(&self, name: String, value: Value) {
+        self.variables.borrow_mut().insert(name, value);
+    }
+
+    // same as set_variable, but name is only copied for new variables
+    pub fn assign_variable(&self, name: &str, value: Value) {
+        self.variables.borrow_mut().assign(name, value);
+    }
+
+    // run "get" command
+    pub fn get(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        self.read_variable(&arguments.pop().unwrap().to_string())
+    }
+
+    // run "move" command
+    pub fn r#move(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        self.move_variable(&arguments.pop().unwrap().to_string())
     }
 
     // run "clone" command
-    pub fn clone(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // give error message if argument count is not matching
-        if callback.len() != 1 {
-            debug::send_argc_message("clone", 1);
+    pub fn clone(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        self.clone_variable(&arguments.pop().unwrap().to_string())
+    }
+
+    // run "drop" command
+    pub fn drop(&self, mut arguments: Vec<Value>) -> Result<Value, JelError> {
+        // drop is same as move, but its name tells value is not needed
+        self.move_variable(&arguments.pop().unwrap().to_string())
+    }
+
+    // borrow or consume variable, depending on read mode
+    pub fn read_variable(&self, name: &str) -> Result<Value, JelError> {
+        if self.borrow_reads.get() {
+            self.clone_variable(name)
+        } else {
+            self.move_variable(name)
         }
+    }
 
-        // get argument
-        let first_arg = callback.pop().unwrap();
+    // find variable by key and remove it
+    pub fn move_variable(&self, name: &str) -> Result<Value, JelError> {
+        let will_return = self.variables.borrow_mut().remove(name);
 
-        // get variable name
-        let variable_name = self.token_to_string(first_arg);
+        // return variable
+        will_return.ok_or_else(|| unknown_variable(name))
+    }
 
-        // dbg!(&self.variables);
-        // find variable by key
-        let mut taken = self.variables.take();
-        let will_return = match taken.get_mut(&variable_name) {
-            Some(data) => data.value.clone(),
-            None => {
-                debug::send_message(&format!(
-                    "variable \"{variable_name}\" doesn't exists. (yet?)"
-                ));
-                String::new()
-            }
-        };
+    // find variable by key and copy it
+    pub fn clone_variable(&self, name: &str) -> Result<Value, JelError> {
+        let will_return = self.variables.borrow().get(name).cloned();
 
         // return variable
-        self.variables.set(taken);
-        parser::Token::String(will_return)
+        will_return.ok_or_else(|| unknown_variable(name))
     }
 
-    // run "drop" command
-    pub fn drop(&self, mut callback: Vec<parser::Token>) -> parser::Token {
-        // give error message if argument count is not matching
-        if callback.len() != 1 {
-            debug::send_argc_message("drop", 1);
+    // borrow or consume variable by slot of innermost scope, depending on read mode
+    pub fn read_slot(&self, slot: usize) -> Result<Value, JelError> {
+        if self.borrow_reads.get() {
+            self.clone_slot(slot)
+        } else {
+            self.move_slot(slot)
         }
+    }
+
+    // find variable by slot and remove it
+    pub fn move_slot(&self, slot: usize) -> Result<Value, JelError> {
+        let mut variables = self.variables.borrow_mut();
 
-        // get argument
-        let first_arg = callback.pop().unwrap();
+        match variables.remove_slot(slot) {
+            Some(value) => Ok(value),
+            None => Err(unknown_variable(variables.slot_name(slot))),
+        }
+    }
 
-        // get variable name
-        let variable_name = self.token_to_string(first_arg);
+    // find variable by slot and copy it
+    pub fn clone_slot(&self, slot: usize) -> Result<Value, JelError> {
+        let variables = self.variables.borrow();
 
-        // dbg!(&self.variables);
-        // find variable by key
-        let mut taken = self.variables.take();
-        let will_return = match taken.remove(&variable_name) {
-            Some(data) => data.value,
-            None => {
-                debug::send_message(&format!(
-                    "variable \"{variable_name}\" doesn't exists. (yet?)"
-                ));
-                String::new()
-            }
-        };
+        match variables.get_slot(slot) {
+            Some(value) => Ok(value.clone()),
+            None => Err(unknown_variable(variables.slot_name(slot))),
+        }
+    }
 
-        // return removed data
-        parser::Token::String(will_return)
+    // set variable by slot of innermost scope
+    pub fn assign_slot(&self, slot: usize, value: Value) {
+        self.variables.borrow_mut().assign_slot(slot, value);
     }
 }
+
+// create unknown variable error
+fn unknown_variable(name: &str) -> JelError {
+    JelError::new(
+        ErrorKind::UnknownVariable,
+        format!("variable \"{name}\" doesn't exists. (yet?)"),
+    )
+}
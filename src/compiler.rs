@@ -0,0 +1,425 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::ast;
+use crate::commands::builtin::Builtin;
+use crate::commands::function::{parse_parameters, Body, Parameter};
+use crate::commands::iterate::Binding;
+use crate::commands::registry::{CommandEntry, Registry};
+use crate::commands::variable::Layout;
+use crate::parser;
+use crate::value::Value;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// bytecode instruction, indexes point to tables of the chunk
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    Constant(usize),          // push a constant
+    Pop,                      // remove top value
+    Replace,                  // pop top value and put it in place of the one below
+    Builtin(Builtin, usize),  // run builtin with n values from the stack
+    Command(usize, usize),    // run host command with n values from the stack
+    Get(usize),               // read variable, depending on read mode
+    Move(usize),              // remove variable and push its value
+    Clone(usize),             // push a copy of variable
+    Let(usize),               // pop value to a variable, pushes nil
+    GetSlot(usize),           // same as get, for a variable with a slot
+    MoveSlot(usize),          // same as move, for a variable with a slot
+    CloneSlot(usize),         // same as clone, for a variable with a slot
+    LetSlot(usize),           // same as let, for a variable with a slot
+    Jump(usize),              // jump to instruction
+    JumpIfFalse(usize),       // pop condition, jump to instruction if it is false
+    LoopStart(usize),         // open loop, "break" jumps to given instruction
+    LoopEnd,                  // close innermost loop
+    IterStart(Option<usize>), // pop iterable and variable name, pattern is used for lists and maps
+    IterNext(usize),          // bind next item, jump to given instruction if it is over
+    IterEnd,                  // remove innermost iteration
+    TryStart(usize),          // open try, errors jump to given instruction
+    TryEnd,                   // close innermost try
+    Lookup(usize, usize),     // push function by name, checks argument count
+    Callee(usize),            // turn top value to a function, checks argument count
+    Invoke(usize),            // call function below n values from the stack
+    Function(usize),          // pop name and define function with given body
+    Lambda(usize),            // push lambda with given body
+    Eval(usize),              // run node with tree-walker, for rare commands
+}
+
+// compiled code with its tables
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,               // instructions
+    pub spans: Vec<parser::Span>,    // location of every instruction
+    pub constants: Vec<Value>,       // literal values
+    pub names: Vec<String>,          // interned variable and function names
+    pub commands: Vec<CommandEntry>, // host commands, resolved while compiling
+    pub bindings: Vec<Binding>,      // "(first second)" patterns of loops
+    pub bodies: Vec<Rc<Body>>,       // bodies of "function" and "lambda" commands
+    pub nodes: Vec<ast::Node>,       // commands that are run by tree-walker
+    pub layout: Rc<Layout>,          // variable slots of function body
+    pub version: usize,              // version of the registry chunk is compiled with
+}
+
+// compile commands to a chunk, it leaves output of the last command on the stack.
+// if layout is given, variables with literal names get slots in it and chunk must
+// run in the scope of that layout
+pub fn compile(nodes: &[ast::Node], registry: &Registry, layout: Option<&mut Layout>) -> Chunk {
+    let mut compiler = Compiler {
+        chunk: Chunk {
+            version: registry.version(),
+            ..Chunk::default()
+        },
+        interned: HashMap::new(),
+        registry,
+        layout,
+    };
+    compiler.sequence(nodes, &parser::Span::default());
+    compiler.chunk
+}
+
+// compile a function body, chunk must run in a scope created with its layout
+pub fn compile_body(body: &ast::Node, parameters: &[Parameter], registry: &Registry) -> Chunk {
+    let mut layout = Layout::default();
+    for parameter in parameters {
+        match parameter {
+            Parameter::Required(name) | Parameter::Default(name, _) | Parameter::Rest(name) => {
+                layout.slot(name);
+            }
+        }
+    }
+
+    let mut compiler = Compiler {
+        chunk: Chunk {
+            version: registry.version(),
+            ..Chunk::default()
+        },
+        interned: HashMap::new(),
+        registry,
+        layout: Some(&mut layout),
+    };
+    compiler.expression(body);
+
+    let mut chunk = compiler.chunk;
+    chunk.layout = Rc::new(layout);
+    chunk
+}
+
+// compiler struct
+struct Compiler<'a> {
+    chunk: Chunk,                     // output
+    interned: HashMap<String, usize>, // indexes of names
+    registry: &'a Registry,           // commands are resolved with it
+    layout: Option<&'a mut Layout>,   // variable slots of the scope chunk runs in
+}
+
+// compiler functions
+impl Compiler<'_> {
+    // add instruction and return its index
+    fn emit(&mut self, op: Op, span: &parser::Span) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.spans.push(span.clone());
+        self.chunk.code.len() - 1
+    }
+
+    // index of next instruction
+    fn here(&self) -> usize {
+        self.chunk.code.len()
+    }
+
+    // point jump at given index to next instruction
+    fn patch(&mut self, at: usize) {
+        let target = self.here();
+
+        match &mut self.chunk.code[at] {
+            Op::Jump(to)
+            | Op::JumpIfFalse(to)
+            | Op::LoopStart(to)
+            | Op::IterNext(to)
+            | Op::TryStart(to) => *to = target,
+            op => unreachable!("can't patch {op:?}"),
+        }
+    }
+
+    // add name to chunk once
+    fn intern(&mut self, name: String) -> usize {
+        if let Some(index) = self.interned.get(&name) {
+            return *index;
+        }
+
+        self.chunk.names.push(name.clone());
+        self.interned.insert(name, self.chunk.names.len() - 1);
+        self.chunk.names.len() - 1
+    }
+
+    // emit variable instruction, variables use slots if compiler has a layout
+    fn variable(
+        &mut self,
+        name: String,
+        by_slot: fn(usize) -> Op,
+        by_name: fn(usize) -> Op,
+        span: &parser::Span,
+    ) {
+        let op = match &mut self.layout {
+            Some(layout) => by_slot(layout.slot(&name)),
+            None => by_name(self.intern(name)),
+        };
+
+        self.emit(op, span);
+    }
+
+    // push a constant
+    fn constant(&mut self, value: Value, span: &parser::Span) {
+        self.chunk.constants.push(value);
+        let index = self.chunk.constants.len() - 1;
+        self.emit(Op::Constant(index), span);
+    }
+
+    // run node with tree-walker
+    fn eval(&mut self, node: &ast::Node, span: &parser::Span) {
+        self.chunk.nodes.push(node.clone());
+        let index = self.chunk.nodes.len() - 1;
+        self.emit(Op::Eval(index), span);
+    }
+
+    // compile commands, only output of the last one is kept
+    fn sequence(&mut self, nodes: &[ast::Node], span: &parser::Span) {
+        if nodes.is_empty() {
+            self.constant(Value::Nil, span);
+        }
+
+        for (index, node) in nodes.iter().enumerate() {
+            if index != 0 {
+                self.emit(Op::Pop, span);
+            }
+
+            self.expression(node);
+        }
+    }
+
+    // compile a node that pushes one value
+    fn expression(&mut self, node: &ast::Node) {
+        match node {
+            ast::Node::Literal(literal) => self.constant(
+                Value::from_literal(&literal.value, literal.quoted),
+                &literal.span,
+            ),
+            ast::Node::Call(call) => self.command(node, call),
+            ast::Node::Concat(concat)
+                if Builtin::Concat.arity().accepts(concat.arguments.len()) =>
+            {
+                self.builtin(Builtin::Concat, &concat.arguments, &concat.span)
+            }
+            // tree-walker reports errors
+            ast::Node::Concat(_) | ast::Node::ParamList(_) => self.eval(node, node.span()),
+        }
+    }
+
+    // compile a named command
+    fn command(&mut self, node: &ast::Node, call: &ast::Call) {
+        let arguments = &call.arguments;
+        let span = &call.span;
+
+        match self.registry.get(&call.name) {
+            // tree-walker reports wrong argument counts
+            Some(found) if !found.handler.arity().accepts(arguments.len()) => self.eval(node, span),
+            Some(found) => match found.handler.as_builtin() {
+                Some(builtin) => self.builtin(builtin, arguments, span),
+                None => {
+                    for argument in arguments {
+                        self.expression(argument);
+                    }
+
+                    self.chunk.commands.push(found.clone());
+                    let index = self.chunk.commands.len() - 1;
+                    self.emit(Op::Command(index, arguments.len()), span);
+                }
+            },
+            None => match call.name.as_str() {
+                "do" if !arguments.is_empty() => self.sequence(arguments, span),
+                "if" if arguments.len() == 3 => self.r#if(arguments, span),
+                "while" if arguments.len() == 2 => self.r#while(arguments, span),
+                "for" if arguments.len() == 3 => self.r#for(arguments, span),
+                "try" if arguments.len() == 3 => self.r#try(arguments, span),
+                "function" if arguments.len() == 3 => self.function(node, arguments, span),
+                "lambda" if arguments.len() == 2 => self.lambda(node, arguments, span),
+                "call" if !arguments.is_empty() => {
+                    let argc = arguments.len() - 1;
+                    self.expression(&arguments[0]);
+                    self.emit(Op::Callee(argc), span);
+                    self.call(&arguments[1..], span);
+                }
+                // definitions and imports are not run often
+                "do" | "if" | "while" | "for" | "try" | "call" | "function" | "lambda"
+                | "import" => self.eval(node, span),
+                name => {
+                    let name = self.intern(name.to_string());
+                    self.emit(Op::Lookup(name, arguments.len()), span);
+                    self.call(arguments, span);
+                }
+            },
+        }
+    }
+
+    // compile a builtin, variable commands with literal names use interned names
+    fn builtin(&mut self, builtin: Builtin, arguments: &[ast::Node], span: &parser::Span) {
+        let name = match arguments.first() {
+            Some(ast::Node::Literal(literal)) => {
+                Some(Value::from_literal(&literal.value, literal.quoted).to_string())
+            }
+            _ => None,
+        };
+
+        match (builtin, name) {
+            (Builtin::Get, Some(name)) => self.variable(name, Op::GetSlot, Op::Get, span),
+            (Builtin::Move | Builtin::Drop, Some(name)) => {
+                self.variable(name, Op::MoveSlot, Op::Move, span)
+            }
+            (Builtin::Clone, Some(name)) => self.variable(name, Op::CloneSlot, Op::Clone, span),
+            (Builtin::Let, Some(name)) => {
+                self.expression(&arguments[1]);
+                self.variable(name, Op::LetSlot, Op::Let, span);
+            }
+            _ => {
+                for argument in arguments {
+                    self.expression(argument);
+                }
+
+                self.emit(Op::Builtin(builtin, arguments.len()), span);
+            }
+        }
+    }
+
+    // compile arguments of a function call, function is already on the stack
+    fn call(&mut self, arguments: &[ast::Node], span: &parser::Span) {
+        for argument in arguments {
+            self.expression(argument);
+        }
+
+        self.emit(Op::Invoke(arguments.len()), span);
+    }
+
+    // add body of "function" or "lambda" to chunk, so every function made by
+    // the command shares its compiled code. it fails if parameters are wrong
+    fn body(&mut self, parameters: &ast::Node, value: &ast::Node) -> Option<usize> {
+        let parameters = parse_parameters(parameters).ok()?;
+        self.chunk
+            .bodies
+            .push(Rc::new(Body::new(parameters, value.clone())));
+        Some(self.chunk.bodies.len() - 1)
+    }
+
+    // compile "function" command, tree-walker reports wrong parameters
+    fn function(&mut self, node: &ast::Node, arguments: &[ast::Node], span: &parser::Span) {
+        match self.body(&arguments[1], &arguments[2]) {
+            Some(body) => {
+                self.expression(&arguments[0]);
+                self.emit(Op::Function(body), span);
+            }
+            None => self.eval(node, span),
+        }
+    }
+
+    // compile "lambda" command, tree-walker reports wrong parameters
+    fn lambda(&mut self, node: &ast::Node, arguments: &[ast::Node], span: &parser::Span) {
+        match self.body(&arguments[0], &arguments[1]) {
+            Some(body) => {
+                self.emit(Op::Lambda(body), span);
+            }
+            None => self.eval(node, span),
+        }
+    }
+
+    // compile "if" command
+    fn r#if(&mut self, arguments: &[ast::Node], span: &parser::Span) {
+        self.expression(&arguments[0]);
+        let jump_to_false = self.emit(Op::JumpIfFalse(0), span);
+        self.expression(&arguments[1]);
+        let jump_to_end = self.emit(Op::Jump(0), span);
+        self.patch(jump_to_false);
+        self.expression(&arguments[2]);
+        self.patch(jump_to_end);
+    }
+
+    // compile "while" command, output of the last iteration stays below loop
+    fn r#while(&mut self, arguments: &[ast::Node], span: &parser::Span) {
+        self.constant(Value::Nil, span);
+        let loop_start = self.emit(Op::LoopStart(0), span);
+
+        // "continue" jumps to the instruction after loop start
+        self.expression(&arguments[0]);
+        let jump_to_end = self.emit(Op::JumpIfFalse(0), span);
+        self.expression(&arguments[1]);
+        self.emit(Op::Replace, span);
+        self.emit(Op::Jump(loop_start + 1), span);
+
+        self.patch(jump_to_end);
+        self.patch(loop_start);
+        self.emit(Op::LoopEnd, span);
+    }
+
+    // compile "for" command
+    fn r#for(&mut self, arguments: &[ast::Node], span: &parser::Span) {
+        // literal names get slots, vm uses them if loop runs in the scope of the layout
+        if let Some(layout) = self.layout.as_deref_mut() {
+            match (&arguments[1], Binding::pair(&arguments[1])) {
+                (_, Some(Binding::Pair(first, second))) => {
+                    layout.slot(&first);
+                    layout.slot(&second);
+                }
+                (ast::Node::Literal(literal), _) if !literal.quoted => {
+                    layout.slot(&literal.value);
+                }
+                _ => (),
+            }
+        }
+
+        // variable name is evaluated before the iterable, patterns are known now
+        let binding = Binding::pair(&arguments[1]).map(|binding| {
+            self.chunk.bindings.push(binding);
+            self.chunk.bindings.len() - 1
+        });
+
+        self.expression(&arguments[1]);
+        self.expression(&arguments[0]);
+        self.emit(Op::IterStart(binding), span);
+        self.constant(Value::Nil, span);
+        let loop_start = self.emit(Op::LoopStart(0), span);
+
+        // "continue" jumps to the instruction after loop start
+        let next = self.emit(Op::IterNext(0), span);
+        self.expression(&arguments[2]);
+        self.emit(Op::Replace, span);
+        self.emit(Op::Jump(next), span);
+
+        self.patch(next);
+        self.patch(loop_start);
+        self.emit(Op::LoopEnd, span);
+        self.emit(Op::IterEnd, span);
+    }
+
+    // compile "try" command, variable name stays below the body until it ends
+    fn r#try(&mut self, arguments: &[ast::Node], span: &parser::Span) {
+        self.expression(&arguments[1]);
+        let try_start = self.emit(Op::TryStart(0), span);
+        self.expression(&arguments[0]);
+        self.emit(Op::TryEnd, span);
+        self.emit(Op::Replace, span);
+        let jump_to_end = self.emit(Op::Jump(0), span);
+
+        // vm binds the error and removes variable name before jumping here
+        self.patch(try_start);
+        self.expression(&arguments[2]);
+        self.patch(jump_to_end);
+    }
+}
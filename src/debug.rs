@@ -12,28 +12,242 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::process::exit;
+use crate::parser::Span;
+use std::error::Error;
+use std::fmt;
 
-// output debug message and exit
-pub fn send(line: usize, column: usize, message: &str) {
-    eprintln!("[JEL] at [LINE {line}] [COLUMN {column}]: {message}");
-    exit(1);
+// error kind enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Syntax,          // parser errors
+    Arity,           // wrong argument count
+    UnknownVariable, // variable is not defined (or consumed)
+    UnknownFunction, // function is not defined
+    Type,            // value has a wrong type
+    Index,           // index is out of range
+    Arithmetic,      // division by zero
+    Io,              // file system and stdio errors
+    Ffi,             // dynamic library errors
+    Assertion,       // failed assert command
+    Thrown,          // error thrown by script
+    Break,           // "break" command, caught by loops
+    Continue,        // "continue" command, caught by loops
+    Return,          // "return" command, caught by function calls
+    StackOverflow,   // maximum call depth is exceeded
+    Unreachable,     // code that is never run, only found by checker
 }
 
-// send debug message without halting
-pub fn send_message(message: &str) {
-    eprintln!("[JEL]: {message}");
-    exit(1);
+// error kind functions
+impl ErrorKind {
+    // check if kind is used for control flow instead of a failure
+    pub fn is_control_flow(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Break | ErrorKind::Continue | ErrorKind::Return
+        )
+    }
 }
 
-// send debug command argument count message
-pub fn send_argc_message(name: &str, count: usize) {
-    eprintln!("[JEL]: command \"{name}\" takes {count} argument.");
-    exit(1);
+// implement display for error kind
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorKind::Syntax => "syntax",
+            ErrorKind::Arity => "arity",
+            ErrorKind::UnknownVariable => "unknown variable",
+            ErrorKind::UnknownFunction => "unknown function",
+            ErrorKind::Type => "type",
+            ErrorKind::Index => "index",
+            ErrorKind::Arithmetic => "arithmetic",
+            ErrorKind::Io => "io",
+            ErrorKind::Ffi => "ffi",
+            ErrorKind::Assertion => "assertion",
+            ErrorKind::Thrown => "thrown",
+            ErrorKind::Break => "break",
+            ErrorKind::Continue => "continue",
+            ErrorKind::Return => "return",
+            ErrorKind::StackOverflow => "stack overflow",
+            ErrorKind::Unreachable => "unreachable",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+// call stack frame struct
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub name: String,       // called function or imported file
+    pub span: Option<Span>, // where it is called from
+}
+
+// implement display for frame
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(span) = &self.span {
+            if let Some(file) = &span.file {
+                write!(f, "[{file}] ")?;
+            }
+
+            write!(f, "[LINE {}] [COLUMN {}] ", span.line, span.column)?;
+        }
+
+        write!(f, "in {}", self.name)
+    }
+}
+
+// error struct, returned to the host instead of halting
+#[derive(Debug, Clone)]
+pub struct JelError {
+    pub kind: ErrorKind,       // error kind
+    pub message: String,       // error message
+    pub span: Option<Span>,    // where error happened (if known)
+    pub backtrace: Vec<Frame>, // call stack when error happened, outermost first
+}
+
+// error functions
+impl JelError {
+    // create new error
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            span: None,
+            backtrace: Vec::new(),
+        }
+    }
+
+    // set error location
+    pub fn at(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    // set error location, if it is not set yet
+    pub fn or_at(mut self, span: &Span) -> Self {
+        if self.span.is_none() {
+            self.span = Some(span.clone());
+        }
+
+        self
+    }
+
+    // set call stack, if it is not set yet
+    pub fn or_backtrace(mut self, frames: Vec<Frame>) -> Self {
+        if self.backtrace.is_empty() {
+            self.backtrace = frames;
+        }
+
+        self
+    }
+
+    // control flow that escaped its loop or function is a syntax error
+    pub fn escaped(mut self) -> Self {
+        if self.kind.is_control_flow() {
+            self.kind = ErrorKind::Syntax;
+        }
+
+        self
+    }
+}
+
+// implement display for error
+impl fmt::Display for JelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => {
+                write!(f, "at ")?;
+
+                if let Some(file) = &span.file {
+                    write!(f, "[{file}] ")?;
+                }
+
+                write!(
+                    f,
+                    "[LINE {}] [COLUMN {}]: {}",
+                    span.line, span.column, self.message
+                )
+            }
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for JelError {}
+
+// output debug message, with source excerpt if source is given
+pub fn send(error: &JelError, source: Option<&str>) {
+    match &error.span {
+        Some(span) => {
+            eprintln!("[JEL] {error}");
+
+            if let Some(excerpt) = source.and_then(|source| excerpt(span, source)) {
+                eprintln!("{excerpt}");
+            }
+        }
+        None => eprintln!("[JEL]: {error}"),
+    }
+
+    // print call stack, most recent call is the last
+    if !error.backtrace.is_empty() {
+        eprintln!("[JEL] backtrace (most recent call last):");
+
+        // recursive calls repeat same frame, print it once
+        let lines: Vec<String> = error.backtrace.iter().map(Frame::to_string).collect();
+        let mut index = 0;
+
+        while index < lines.len() {
+            let repeated = lines[index..]
+                .iter()
+                .take_while(|line| **line == lines[index])
+                .count();
+
+            eprintln!("  {}", lines[index]);
+            if repeated > 1 {
+                eprintln!("  ... previous frame repeated {} more times", repeated - 1);
+            }
+
+            index += repeated;
+        }
+    }
+}
+
+// create a caret-underlined source excerpt for given span
+pub fn excerpt(span: &Span, source: &str) -> Option<String> {
+    let line = source.lines().nth(span.line.checked_sub(1)?)?;
+    let line = line.trim_end_matches('\r');
+
+    // keep tabs, so carets line up with the source
+    let offset: String = line
+        .chars()
+        .take(span.column.saturating_sub(1))
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    // don't underline after end of the line
+    let remaining = line.chars().count().saturating_sub(span.column - 1);
+    let carets = "^".repeat(span.length.clamp(1, remaining.max(1)));
+
+    let number = span.line.to_string();
+    let padding = " ".repeat(number.len());
+
+    Some(format!(
+        "{padding} |\n{number} | {line}\n{padding} | {offset}{carets}"
+    ))
+}
+
+// create command argument count error
+pub fn argc_error(name: &str, count: usize) -> JelError {
+    JelError::new(
+        ErrorKind::Arity,
+        format!("command \"{name}\" takes {count} argument."),
+    )
 }
 
-// send debug command argument count message with least
-pub fn send_least_argc_message(name: &str, count: usize) {
-    eprintln!("[JEL]: command \"{name}\" takes least {count} argument.");
-    exit(1);
+// create command argument count error with least
+pub fn least_argc_error(name: &str, count: usize) -> JelError {
+    JelError::new(
+        ErrorKind::Arity,
+        format!("command \"{name}\" takes least {count} argument."),
+    )
 }
@@ -0,0 +1,68 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jel::debug;
+use jel::formatter::format_source;
+use std::fs::{read_to_string, write};
+
+// run "jel fmt", returns exit code
+// files are formatted in place, "--check" only reports files that would change
+pub fn run(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let files: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+
+    if files.is_empty() {
+        eprintln!("[JEL]: usage: jel fmt [--check] <files>");
+        return 2;
+    }
+
+    let mut failed = false;
+    for file in files {
+        let source = match read_to_string(file) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("[JEL] at [READING]: can't read file \"{file}\": {err}");
+                failed = true;
+                continue;
+            }
+        };
+
+        // files with syntax errors are left as they are
+        let formatted = match format_source(&source, file) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for err in &errors {
+                    debug::send(err, Some(&source));
+                }
+
+                failed = true;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            eprintln!("[JEL] \"{file}\" is not formatted.");
+            failed = true;
+        } else if let Err(err) = write(file, formatted) {
+            eprintln!("[JEL] at [WRITING]: can't write file \"{file}\": {err}");
+            failed = true;
+        }
+    }
+
+    i32::from(failed)
+}
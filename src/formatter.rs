@@ -0,0 +1,368 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::ast::{Comment, Literal, Node, Param, ParamList};
+use crate::debug::JelError;
+use crate::parser::{Parser, Span};
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+// commands longer than this are broken into lines
+const MAX_WIDTH: usize = 80;
+
+// indentation of arguments in broken commands
+const INDENT: usize = 4;
+
+// format source code, sources with syntax errors are not formatted
+pub fn format_source(source: &str, file: &str) -> Result<String, Vec<JelError>> {
+    let mut parser = Parser::with_file(source, file);
+    if parser.parse().is_err() {
+        return Err(parser.diagnostics);
+    }
+
+    let mut formatter = Formatter {
+        source,
+        line_starts: line_starts(source),
+        output: String::new(),
+        comments: parser.comments.into_iter().peekable(),
+        last_line: None,
+    };
+    formatter.sequence(&parser.output, 0, None);
+
+    if !formatter.output.is_empty() {
+        formatter.output.push('\n');
+    }
+
+    Ok(formatter.output)
+}
+
+// formatter struct
+struct Formatter<'a> {
+    source: &'a str,         // source code, for strings that are kept as written
+    line_starts: Vec<usize>, // byte index of every line in source
+    output: String,          // formatted source
+    comments: Peekable<IntoIter<Comment>>, // comments that are not written yet
+    last_line: Option<usize>, // source line of last written node, none at start of a block
+}
+
+// formatter functions
+impl Formatter<'_> {
+    // start a new line with given indentation
+    fn new_line(&mut self, indent: usize) {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+
+        self.output.push_str(&" ".repeat(indent));
+    }
+
+    // keep one empty line if source has empty lines before given line
+    fn keep_empty_line(&mut self, line: usize) {
+        if matches!(self.last_line, Some(last) if line > last + 1) {
+            self.output.push('\n');
+        }
+    }
+
+    // column of the next character
+    fn column(&self) -> usize {
+        let line = self.output.rsplit('\n').next().unwrap_or_default();
+        line.chars().count()
+    }
+
+    // write nodes on their own lines, with comments between them
+    fn sequence(&mut self, nodes: &[Node], indent: usize, end: Option<&Span>) {
+        for node in nodes {
+            self.comments_before(position(node.span()), indent);
+            self.keep_empty_line(node.span().line);
+            self.new_line(indent);
+            self.node(node, indent);
+            self.last_line = Some(end_line(node));
+        }
+
+        // comments before closing brace, or end of the source
+        let end = end.map_or((usize::MAX, 0), position);
+        self.comments_before(end, indent);
+    }
+
+    // write comments that come before given position
+    fn comments_before(&mut self, end: (usize, usize), indent: usize) {
+        while let Some(comment) = self
+            .comments
+            .next_if(|comment| position(&comment.span) < end)
+        {
+            if comment.own_line || self.output.is_empty() {
+                self.keep_empty_line(comment.span.line);
+                self.new_line(indent);
+            } else {
+                self.output.push(' ');
+            }
+
+            self.output.push('#');
+            self.output.push_str(&comment.text);
+            self.last_line = Some(comment.span.line);
+        }
+    }
+
+    // check if a comment is written inside of given node
+    fn has_comment_inside(&mut self, node: &Node) -> bool {
+        let end = match node {
+            Node::Call(call) => &call.end,
+            Node::Concat(concat) => &concat.end,
+            Node::ParamList(list) => &list.end,
+            Node::Literal(_) => return false,
+        };
+
+        matches!(self.comments.peek(), Some(comment) if position(&comment.span) < position(end))
+    }
+
+    // write a node, it is broken into lines if it doesn't fit
+    fn node(&mut self, node: &Node, indent: usize) {
+        let flat = self.flat(node);
+
+        if !has_block(node)
+            && !self.has_comment_inside(node)
+            && self.column() + flat.chars().count() <= MAX_WIDTH
+        {
+            self.output.push_str(&flat);
+            return;
+        }
+
+        match node {
+            Node::Call(call) => self.group(
+                &format!("{}(", call.name),
+                &call.arguments,
+                &call.end,
+                indent,
+                !is_do(node),
+            ),
+            Node::Concat(concat) => self.group("(", &concat.arguments, &concat.end, indent, true),
+            Node::ParamList(list) => self.parameters(list, indent),
+            // literals are never broken
+            Node::Literal(_) => self.output.push_str(&flat),
+        }
+    }
+
+    // write a parameter list with comments inside, every parameter on its own line
+    fn parameters(&mut self, list: &ParamList, indent: usize) {
+        self.output.push('(');
+        self.last_line = None;
+
+        for parameter in &list.parameters {
+            let name = match parameter {
+                Param::Name(name) | Param::Default(name, _) | Param::Rest(name) => name,
+            };
+
+            self.comments_before(position(&name.span), indent + INDENT);
+            self.keep_empty_line(name.span.line);
+            self.new_line(indent + INDENT);
+
+            match parameter {
+                Param::Default(name, value) => {
+                    let comment_before_value = matches!(
+                        self.comments.peek(),
+                        Some(comment) if position(&comment.span) < position(value.span())
+                    );
+
+                    // comments between name and default value
+                    self.output.push('(');
+                    self.output.push_str(&self.literal(name));
+                    if comment_before_value {
+                        self.comments_before(position(value.span()), indent + INDENT * 2);
+                        self.new_line(indent + INDENT * 2);
+                    } else {
+                        self.output.push(' ');
+                    }
+
+                    self.node(value, indent + INDENT * 2);
+                    self.output.push(')');
+                    self.last_line = Some(end_line(value));
+                }
+                _ => {
+                    self.output.push_str(&self.flat_parameter(parameter));
+                    self.last_line = Some(name.span.line);
+                }
+            }
+        }
+
+        // comments before closing brace
+        self.comments_before(position(&list.end), indent + INDENT);
+        self.new_line(indent);
+        self.output.push(')');
+    }
+
+    // write a broken command
+    fn group(&mut self, open: &str, arguments: &[Node], end: &Span, indent: usize, hug: bool) {
+        // other arguments stay on the first line if only the last command needs to be broken,
+        // commands of do blocks are always on their own lines
+        if let Some((last, rest)) = arguments.split_last() {
+            let mut head = open.to_string();
+            for argument in rest {
+                head.push_str(&self.flat(argument));
+                head.push(' ');
+            }
+
+            let comment_in_head = matches!(
+                self.comments.peek(),
+                Some(comment) if position(&comment.span) < position(last.span())
+            );
+
+            let breakable = is_do(last)
+                || (!rest.is_empty() && matches!(last, Node::Call(_) | Node::Concat(_)));
+
+            if hug
+                && breakable
+                && !rest.iter().any(has_block)
+                && !comment_in_head
+                && self.column() + head.chars().count() < MAX_WIDTH
+            {
+                if is_do(last) {
+                    // blocks are opened on the first line, like "if(condition do("
+                    self.output.push_str(&head);
+                    self.node(last, indent);
+                } else {
+                    // last argument is written on its own line, like "for(get(items) item"
+                    self.output.push_str(head.trim_end());
+                    self.last_line = None;
+                    self.sequence(std::slice::from_ref(last), indent + INDENT, Some(end));
+                    self.new_line(indent);
+                }
+
+                self.output.push(')');
+                return;
+            }
+        }
+
+        // every argument on its own line
+        self.output.push_str(open);
+        self.last_line = None;
+        self.sequence(arguments, indent + INDENT, Some(end));
+        self.new_line(indent);
+        self.output.push(')');
+    }
+
+    // write a node on a single line
+    fn flat(&self, node: &Node) -> String {
+        match node {
+            Node::Call(call) => format!("{}({})", call.name, self.flat_list(&call.arguments)),
+            Node::Concat(concat) => format!("({})", self.flat_list(&concat.arguments)),
+            Node::ParamList(list) => {
+                let parameters: Vec<String> = list
+                    .parameters
+                    .iter()
+                    .map(|parameter| self.flat_parameter(parameter))
+                    .collect();
+
+                format!("({})", parameters.join(" "))
+            }
+            Node::Literal(node) => self.literal(node),
+        }
+    }
+
+    // write a parameter on a single line
+    fn flat_parameter(&self, parameter: &Param) -> String {
+        match parameter {
+            Param::Name(name) => self.literal(name),
+            Param::Default(name, value) => format!("({} {})", self.literal(name), self.flat(value)),
+            Param::Rest(name) => format!("...{}", self.literal(name)),
+        }
+    }
+
+    // write nodes on a single line, separated with spaces
+    fn flat_list(&self, nodes: &[Node]) -> String {
+        let nodes: Vec<String> = nodes.iter().map(|node| self.flat(node)).collect();
+        nodes.join(" ")
+    }
+
+    // write a literal, raw and triple-quoted strings are kept as they are written
+    fn literal(&self, literal: &Literal) -> String {
+        if !literal.quoted {
+            return literal.value.clone();
+        }
+
+        let span = &literal.span;
+        let written: String = self.source[self.line_starts[span.line - 1]..]
+            .chars()
+            .skip(span.column - 1)
+            .take(span.length)
+            .collect();
+
+        if written.starts_with("r\"") || written.starts_with("\"\"\"") {
+            written
+        } else {
+            quote(&literal.value)
+        }
+    }
+}
+
+// check if node is a do block
+fn is_do(node: &Node) -> bool {
+    matches!(node, Node::Call(call) if call.name == "do" && !call.arguments.is_empty())
+}
+
+// check if node has a do block that is always broken, blocks with multiple commands
+// and blocks that are already broken in the source are kept on their own lines
+fn has_block(node: &Node) -> bool {
+    match node {
+        Node::Call(call) => {
+            (is_do(node) && (call.arguments.len() > 1 || call.span.line != call.end.line))
+                || call.arguments.iter().any(has_block)
+        }
+        Node::Concat(concat) => concat.arguments.iter().any(has_block),
+        Node::ParamList(_) | Node::Literal(_) => false,
+    }
+}
+
+// write a string in quotes, with escapes
+fn quote(value: &str) -> String {
+    let mut output = String::from("\"");
+
+    for character in value.chars() {
+        match character {
+            '\\' => output.push_str("\\\\"),
+            '"' => output.push_str("\\\""),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            '\r' => output.push_str("\\r"),
+            '\0' => output.push_str("\\0"),
+            character if character.is_control() => {
+                output.push_str(&format!("\\u{{{:X}}}", character as u32))
+            }
+            character => output.push(character),
+        }
+    }
+
+    output.push('"');
+    output
+}
+
+// byte indexes where lines start
+fn line_starts(source: &str) -> Vec<usize> {
+    let lines = source.match_indices('\n').map(|(index, _)| index + 1);
+    std::iter::once(0).chain(lines).collect()
+}
+
+// position of a span, for ordering
+fn position(span: &Span) -> (usize, usize) {
+    (span.line, span.column)
+}
+
+// last source line of a node
+fn end_line(node: &Node) -> usize {
+    match node {
+        Node::Call(call) => call.end.line,
+        Node::Concat(concat) => concat.end.line,
+        Node::ParamList(list) => list.end.line,
+        Node::Literal(literal) => literal.span.line + literal.value.matches('\n').count(),
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::debug::{ErrorKind, JelError};
+use crate::machine;
+use crate::parser;
+use crate::value::Value;
+use std::fs::read_to_string;
+use std::path::Path;
+
+// embeddable interpreter, keeps one machine alive between evaluations
+#[derive(Default)]
+pub struct Interpreter {
+    machine: machine::Machine,
+}
+
+impl Interpreter {
+    // create new interpreter with an empty machine
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // create new interpreter that runs scripts with given backend
+    pub fn with_backend(backend: machine::Backend) -> Self {
+        let interpreter = Self::default();
+        interpreter.machine.backend.set(backend);
+        interpreter
+    }
+
+    // parse and run given source, returns output of the last command
+    pub fn eval_str(&mut self, source: &str) -> Result<Value, JelError> {
+        self.eval(parser::Parser::new(source))
+    }
+
+    // same as eval_str, but errors will point given file name
+    pub fn eval_source(&mut self, source: &str, file: &str) -> Result<Value, JelError> {
+        self.eval(parser::Parser::with_file(source, file))
+    }
+
+    // run parser and evaluate its output
+    fn eval(&mut self, mut parser: parser::Parser) -> Result<Value, JelError> {
+        parser.parse()?;
+
+        // run commands on the same machine
+        self.machine.run_nodes(&parser.output)
+    }
+
+    // read a file and run it
+    pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Value, JelError> {
+        let path = path.as_ref();
+
+        match read_to_string(path) {
+            Ok(source) => self.eval_source(&source, &path.display().to_string()),
+            Err(err) => Err(JelError::new(
+                ErrorKind::Io,
+                format!("can't read file \"{}\": {err}", path.display()),
+            )),
+        }
+    }
+
+    // return underlying machine
+    pub fn machine(&self) -> &machine::Machine {
+        &self.machine
+    }
+
+    // return underlying machine as mutable
+    pub fn machine_mut(&mut self) -> &mut machine::Machine {
+        &mut self.machine
+    }
+}
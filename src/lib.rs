@@ -0,0 +1,33 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod ast;
+pub mod checker;
+pub mod commands;
+pub mod compiler;
+pub mod debug;
+pub mod formatter;
+pub mod interpreter;
+pub mod machine;
+pub mod parser;
+pub mod server;
+pub mod value;
+pub mod vm;
+
+pub use commands::registry::{Arity, CommandHandler};
+pub use debug::JelError;
+pub use interpreter::Interpreter;
+pub use machine::Machine;
+pub use parser::Parser;
+pub use value::Value;
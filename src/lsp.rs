@@ -0,0 +1,88 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jel::server::{error_response, Server, PARSE_ERROR};
+use serde_json::{json, Value as Json};
+use std::io::{stdin, stdout, BufRead, Write};
+
+// run "jel lsp", messages are read from stdin and written to stdout
+pub fn run() -> i32 {
+    let mut server = Server::new();
+    let mut input = stdin().lock();
+    let mut output = stdout().lock();
+
+    loop {
+        // end of input is same as "exit" notification
+        let body = match read_message(&mut input) {
+            Ok(Some(body)) => body,
+            Ok(None) => {
+                server.handle(&json!({ "jsonrpc": "2.0", "method": "exit" }));
+                return server.exit_code().unwrap_or(1);
+            }
+            Err(err) => {
+                eprintln!("[JEL] at [READING]: can't read message: {err}");
+                return 1;
+            }
+        };
+
+        let replies = match serde_json::from_slice::<Json>(&body) {
+            Ok(message) => server.handle(&message),
+            Err(err) => vec![error_response(Json::Null, PARSE_ERROR, &err.to_string())],
+        };
+
+        for reply in replies {
+            if let Err(err) = write_message(&mut output, &reply) {
+                eprintln!("[JEL] at [WRITING]: can't write message: {err}");
+                return 1;
+            }
+        }
+
+        if let Some(code) = server.exit_code() {
+            return code;
+        }
+    }
+}
+
+// read a message body, messages start with "Content-Length" header
+fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        // headers end with an empty line
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or_default()];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+// write a message with its header
+fn write_message(output: &mut impl Write, message: &Json) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
@@ -12,19 +12,115 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::ast;
 use crate::commands;
+use crate::commands::builtin::Builtin;
+use crate::commands::registry::Arity;
+use crate::compiler;
 use crate::debug;
-use crate::parser;
-use std::cell::Cell;
+use crate::debug::{ErrorKind, JelError};
+use crate::value::Value;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::mem::take;
+use std::rc::Rc;
 
-// machine struct
+// default maximum call depth, deeper calls raise an error instead of crashing
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+// calls and imports continue on a new stack segment if less than red zone is left,
+// so call depth is only limited by maximum call depth, not by stack of the thread
+pub const STACK_RED_ZONE: usize = 256 * 1024;
+pub const STACK_SEGMENT_SIZE: usize = 4 * 1024 * 1024;
+
+// command that decides what to evaluate, they are run by machine itself
+#[derive(Debug, Clone, Copy)]
+pub struct SpecialForm {
+    pub name: &'static str,        // command name
+    pub arity: Arity,              // accepted argument count
+    pub usage: &'static str,       // how it is written
+    pub description: &'static str, // what it does
+}
+
+// special forms, in the order of process function
+pub const SPECIAL_FORMS: &[SpecialForm] = &[
+    SpecialForm {
+        name: "do",
+        arity: Arity::AtLeast(1),
+        usage: "do(commands...)",
+        description: "runs commands in order and returns output of the last one.",
+    },
+    SpecialForm {
+        name: "function",
+        arity: Arity::Exact(3),
+        usage: "function(name (parameters...) body)",
+        description: "defines a function, parameters can be a name, (name default) or ...rest.",
+    },
+    SpecialForm {
+        name: "lambda",
+        arity: Arity::Exact(2),
+        usage: "lambda((parameters...) body)",
+        description: "creates a function value that captures variables of current scope.",
+    },
+    SpecialForm {
+        name: "call",
+        arity: Arity::AtLeast(1),
+        usage: "call(function arguments...)",
+        description: "calls a lambda or a function by name.",
+    },
+    SpecialForm {
+        name: "if",
+        arity: Arity::Exact(3),
+        usage: "if(condition then otherwise)",
+        description: "runs then if condition is true, otherwise runs otherwise.",
+    },
+    SpecialForm {
+        name: "for",
+        arity: Arity::Exact(3),
+        usage: "for(iterable variable body)",
+        description: "runs body for every item of a list, map, number or string, (first second) gets index and item or key and value.",
+    },
+    SpecialForm {
+        name: "while",
+        arity: Arity::Exact(2),
+        usage: "while(condition body)",
+        description: "runs body while condition is true.",
+    },
+    SpecialForm {
+        name: "try",
+        arity: Arity::Exact(3),
+        usage: "try(body variable handler)",
+        description: "runs handler with the error as a map in variable if body fails.",
+    },
+    SpecialForm {
+        name: "import",
+        arity: Arity::AtLeast(1),
+        usage: "import(path)",
+        description: "runs a file and copies its functions and global variables.",
+    },
+];
+
+// way of running scripts and function bodies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Vm, // compile to bytecode and run on a stack machine
+    Tree, // walk syntax tree, kept as reference for differential testing
+}
+
+// machine struct, shared state is borrowed only for a moment so commands
+// that run while another one is evaluated always see the same data
 pub struct Machine {
-    pub instructions: Vec<parser::Token>, // instructions for machine
-    pub variables: Cell<HashMap<String, commands::variable::VariableData>>, // variables are stored here
-    pub functions: Cell<HashMap<String, commands::function::FunctionData>>, // functions are stored here
-    pub dynamic_libs: Cell<Vec<commands::dylib::DynamicLibraryData>>, // dynamic libraries are stored here
+    pub instructions: Vec<ast::Node>, // instructions for machine
+    pub variables: RefCell<commands::variable::Scopes>, // variables are stored here
+    pub functions: RefCell<HashMap<String, Rc<commands::function::FunctionData>>>, // functions are stored here
+    pub dynamic_libs: RefCell<Vec<commands::dylib::DynamicLibraryData>>, // dynamic libraries are stored here
+    pub borrow_reads: Cell<bool>, // if true, "get" doesn't consume variables
+    pub return_value: Cell<Value>, // value of the last "return" command
+    pub call_stack: RefCell<Vec<debug::Frame>>, // active function calls and imports
+    pub max_depth: Cell<usize>,   // maximum size of call stack
+    pub backend: Cell<Backend>,   // how scripts and function bodies are run
+    pub registry: RefCell<commands::registry::Registry>, // builtins and commands of the host program
 }
 
 // implement default for machine
@@ -33,9 +129,15 @@ impl Default for Machine {
     fn default() -> Self {
         Self {
             instructions: Vec::new(),
-            variables: Cell::new(HashMap::new()),
-            functions: Cell::new(HashMap::new()),
-            dynamic_libs: Cell::new(Vec::new()),
+            variables: RefCell::new(commands::variable::Scopes::default()),
+            functions: RefCell::new(HashMap::new()),
+            dynamic_libs: RefCell::new(Vec::new()),
+            borrow_reads: Cell::new(false),
+            return_value: Cell::new(Value::Nil),
+            call_stack: RefCell::new(Vec::new()),
+            max_depth: Cell::new(DEFAULT_MAX_DEPTH),
+            backend: Cell::new(Backend::default()),
+            registry: RefCell::new(commands::registry::Registry::new()),
         }
     }
 }
@@ -43,7 +145,7 @@ impl Default for Machine {
 // main part of the machine
 impl Machine {
     // add new command to create machine easily
-    pub fn new(instructions: Vec<parser::Token>) -> Self {
+    pub fn new(instructions: Vec<ast::Node>) -> Self {
         Machine {
             instructions,
             ..Default::default()
@@ -51,123 +153,100 @@ impl Machine {
     }
 
     // run machine
-    pub fn process_whole(&mut self) {
-        // iterate over instructions
-        for token in take(&mut self.instructions) {
-            self.process(token);
-        }
+    pub fn process_whole(&mut self) -> Result<(), JelError> {
+        let instructions = take(&mut self.instructions);
+        self.run_nodes(&instructions)?;
+        Ok(())
     }
 
-    // convert a token to a string
-    pub fn token_to_string(&self, token: parser::Token) -> String {
-        if let parser::Token::String(value) = token {
-            // return string
-            value
-        } else if matches!(token, parser::Token::Command(_)) {
-            // run command and push string
-            if let parser::Token::String(value) = self.process(token) {
-                // push string
-                value
-            } else {
-                debug::send_message("token must be a valid object.");
-                String::new()
+    // run top-level commands with selected backend, returns output of the last one
+    pub fn run_nodes(&self, nodes: &[ast::Node]) -> Result<Value, JelError> {
+        match self.backend.get() {
+            Backend::Vm => {
+                let chunk = self.compile(nodes);
+                self.run_chunk(&chunk).map_err(JelError::escaped)
+            }
+            Backend::Tree => {
+                // iterate over instructions
+                let mut last_output = Value::Nil;
+                for node in nodes {
+                    last_output = self.process(node).map_err(JelError::escaped)?;
+                }
+
+                Ok(last_output)
             }
+        }
+    }
+
+    // run a call or an import, stack is grown on the heap if it runs low
+    pub fn grow_stack<R>(&self, function: impl FnOnce() -> R) -> R {
+        stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, function)
+    }
+
+    // compile commands with registered commands of this machine, variables get
+    // slots of global scope if commands run in it
+    pub fn compile(&self, nodes: &[ast::Node]) -> compiler::Chunk {
+        let registry = self.registry.borrow();
+        let mut variables = self.variables.borrow_mut();
+
+        if variables.depth() == 1 {
+            variables
+                .global_mut()
+                .extend_layout(|layout| compiler::compile(nodes, &registry, Some(layout)))
         } else {
-            debug::send_message("token must be a valid object.");
-            String::new()
+            compiler::compile(nodes, &registry, None)
         }
     }
 
-    // run a command
-    pub fn process(&self, token: parser::Token) -> parser::Token {
-        // check if its a command
-        if let parser::Token::Command(mut command) = token {
-            match command.name.as_str() {
-                // from commands/process.rs
-                "print" => self.print(command.arguments),
-                "input" => self.input(command.arguments),
-                "sleep" => self.sleep(command.arguments),
-                // from commands/variable.rs
-                "let" => self.r#let(command.arguments),
-                "get" => self.get(command.arguments),
-                "clone" => self.clone(command.arguments),
-                "drop" => self.drop(command.arguments),
-                // from commands/function.rs
-                "do" => self.r#do(command.arguments),
-                "function" => self.function(command.arguments),
-                "call" => self.call(command.arguments),
-                // from commands/dylib.rs
-                "dylib" => self.dylib(command.arguments),
-                "native" => self.native(command.arguments),
-                // from commands/import.rs
-                "import" => self.import(command.arguments),
-                // from commands/compare.rs
-                "if" => self.r#if(command.arguments),
-                "equals" => self.equals(command.arguments),
-                "not" => self.not(command.arguments),
-                "bigger" => self.bigger(command.arguments),
-                "smaller" => self.smaller(command.arguments),
-                "assert" => self.assert(command.arguments),
-                // from commands/iterate.rs
-                "for" => self.r#for(command.arguments),
-                "while" => self.r#while(command.arguments),
-                // from commands/cast.rs
-                "float" => self.float(command.arguments),
-                "int" => self.int(command.arguments),
-                "bool" => self.bool(command.arguments),
-                "type" => self.r#type(command.arguments),
-                // from commands/math.rs
-                "+" => self.add(command.arguments),
-                "-" => self.sub(command.arguments),
-                "*" => self.mul(command.arguments),
-                "/" => self.div(command.arguments),
-                "%" => self.r#mod(command.arguments),
-                // from commands/fs.rs
-                "file.read" => self.readf(command.arguments),
-                "file.write" => self.writef(command.arguments),
-                "file.exists" => self.existsf(command.arguments),
-                "file.make" => self.makef(command.arguments),
-                "file.remove" => self.removef(command.arguments),
-                "file.append" => self.appendf(command.arguments),
-                "file.type" => self.typef(command.arguments),
-                // empty command is for concat objects
-                "" => {
-                    // check arguments
-                    if command.arguments.len() < 2 {
-                        debug::send_least_argc_message("concat", 2);
-                    }
-
-                    command.arguments.reverse();
-                    let connector = self.token_to_string(command.arguments.pop().unwrap());
-                    command.arguments.reverse();
-                    let mut arguments: Vec<String> = Vec::new();
-
-                    // iterate over given arguments
-                    for arg in command.arguments {
-                        // check token type
-                        if let parser::Token::String(value) = arg {
-                            // push string
-                            arguments.push(value);
-                        } else if let parser::Token::Command(_) = arg {
-                            // run command and push string
-                            if let parser::Token::String(value) = self.process(arg) {
-                                // push string
-                                arguments.push(value);
-                            }
-                        }
-                    }
-
-                    // return object
-                    let calculated_string = arguments.join(&connector);
-                    parser::Token::String(calculated_string)
-                }
-                _ => {
-                    command.arguments.insert(0, crate::to_token!(command.name));
-                    self.call(command.arguments)
-                }
+    // convert a node to a string
+    pub fn node_to_string(&self, node: &ast::Node) -> Result<String, JelError> {
+        Ok(self.process(node)?.to_string())
+    }
+
+    // run a node, nodes are evaluated by reference so bodies are never copied
+    pub fn process(&self, node: &ast::Node) -> Result<Value, JelError> {
+        match node {
+            ast::Node::Call(call) => {
+                // errors without location will point this command
+                let span = &call.span;
+                let arguments = &call.arguments;
+
+                // registered commands get evaluated arguments, others decide what to evaluate
+                let result = match self.find_command(&call.name) {
+                    Some(found) => self.run_command(&found.name, found.handler.as_ref(), arguments),
+                    None => match call.name.as_str() {
+                        // from commands/function.rs
+                        "do" => self.r#do(arguments),
+                        "function" => self.function(arguments),
+                        "lambda" => self.lambda(arguments),
+                        "call" => self.call(arguments, span),
+                        // from commands/compare.rs
+                        "if" => self.r#if(arguments),
+                        // from commands/iterate.rs
+                        "for" => self.r#for(arguments),
+                        "while" => self.r#while(arguments),
+                        // from commands/error.rs
+                        "try" => self.r#try(arguments),
+                        // from commands/import.rs
+                        "import" => self.import(arguments, span),
+                        // other names are functions
+                        name => self.call_with(Value::from(name), arguments, span),
+                    },
+                };
+
+                result.map_err(|err| err.or_at(span))
             }
-        } else {
-            token
+            ast::Node::Concat(concat) => self
+                .run_command("concat", &Builtin::Concat, &concat.arguments)
+                .map_err(|err| err.or_at(&concat.span)),
+            // parameters are only read by commands that define them
+            ast::Node::ParamList(list) => Err(JelError::new(
+                ErrorKind::Syntax,
+                "parameter list can't be used as a value.",
+            )
+            .at(list.span.clone())),
+            // convert literal to a value
+            ast::Node::Literal(literal) => Ok(Value::from_literal(&literal.value, literal.quoted)),
         }
     }
 }
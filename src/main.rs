@@ -11,21 +11,49 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-mod commands;
-mod debug;
-mod machine;
-mod macros;
-mod parser;
 
+mod check;
+mod fmt;
+mod lsp;
+mod repl;
+
+use jel::debug;
+use jel::machine::Backend;
+use jel::{Interpreter, Parser};
 use std::env;
 use std::fs::read_to_string;
+use std::process::exit;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    // "jel fmt" formats files instead of running them
+    if args.get(1).map(String::as_str) == Some("fmt") {
+        exit(fmt::run(&args[2..]));
+    }
 
-    // check command line count
+    // "jel check" finds mistakes without running files
+    if args.get(1).map(String::as_str) == Some("check") {
+        exit(check::run(&args[2..]));
+    }
+
+    // "jel lsp" starts a language server on stdin and stdout
+    if args.get(1).map(String::as_str) == Some("lsp") {
+        exit(lsp::run());
+    }
+
+    // "--tree" runs scripts with tree-walker instead of vm
+    let backend = match args.iter().position(|arg| arg == "--tree") {
+        Some(index) => {
+            args.remove(index);
+            Backend::Tree
+        }
+        None => Backend::Vm,
+    };
+
+    // start repl if file is not given
     if args.len() < 2 {
-        eprintln!("[JEL] at [READING]: file name must be given.");
+        repl::Repl::new(backend).run();
         return;
     }
 
@@ -36,17 +64,37 @@ fn main() {
     let file_name = &args[1];
     path.push(file_name);
 
-    let file_data = read_to_string(&path).unwrap();
+    let file_data = match read_to_string(&path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("[JEL] at [READING]: can't read file \"{file_name}\": {err}");
+            exit(1);
+        }
+    };
     path.pop();
     let _ = env::set_current_dir(path);
 
-    // run parser
-    let mut parser = parser::Parser::new(&file_data);
-    parser.parse();
+    // parse file, every syntax error is printed at once
+    let mut parser = Parser::with_file(&file_data, file_name);
+    if parser.parse().is_err() {
+        for err in &parser.diagnostics {
+            debug::send(err, Some(&file_data));
+        }
 
-    // dbg!(&parser.output);
+        exit(1);
+    }
 
     // run interpreter
-    let mut machine = machine::Machine::new(parser.output);
-    machine.process_whole();
+    let interpreter = Interpreter::with_backend(backend);
+    if let Err(err) = interpreter.machine().run_nodes(&parser.output) {
+        // find source of the file that error happened
+        let source = match err.span.as_ref().and_then(|span| span.file.as_deref()) {
+            Some(file) if file == file_name.as_str() => Some(file_data),
+            Some(file) => read_to_string(file).ok(),
+            None => None,
+        };
+
+        debug::send(&err, source.as_deref());
+        exit(1);
+    }
 }
@@ -12,38 +12,34 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::debug;
+use crate::ast::{Call, Comment, Concat, Literal, Node, Param, ParamList};
+use crate::debug::{ErrorKind, JelError};
 use std::mem::take;
+use std::rc::Rc;
 
 // parser state enum
 #[derive(Debug)]
 pub enum ParserState {
     Token,
-    String,
+    String { raw: bool, triple: bool }, // raw strings don't have escapes, triple-quoted ones can have new lines
     Comment,
 }
 
-// token enum
-#[derive(Debug, Clone)]
-pub enum Token {
-    Command(Command), // for commands
-    String(String),   // for strings
-    PlaceHolder(u8),  // for placeholders
+// source span struct
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    pub file: Option<Rc<str>>, // file name (if known)
+    pub line: usize,           // line number, starts from 1
+    pub column: usize,         // column number, starts from 1
+    pub length: usize,         // length of spanned text
 }
 
-// implement default for token
-impl Default for Token {
-    // add default function for default trait
-    fn default() -> Self {
-        Token::PlaceHolder(0xF)
-    }
-}
-
-// command struct
-#[derive(Debug, Clone, Default)]
-pub struct Command {
-    pub name: String,          // command name
-    pub arguments: Vec<Token>, // command arguments
+// braces that are not closed yet
+#[derive(Debug)]
+struct Group {
+    name: String,         // command name, empty for braces without a name
+    arguments: Vec<Node>, // arguments collected so far
+    span: Span,           // where command name (or the brace) is written
 }
 
 // parser struct
@@ -52,11 +48,23 @@ pub struct Parser<'a> {
     // these objects for debugging
     line: usize,
     column: usize,
-
-    source: &'a str,        // given source
-    temp: String,           // temporary string to keep collected token
-    pub output: Vec<Token>, // parser output
-    state: ParserState,     // parser state (normal or string collecting)
+    file: Option<Rc<str>>,
+    temp_start: (usize, usize),
+    escape_start: (usize, usize),
+    comment_own_line: bool,
+
+    source: &'a str,                // given source
+    position: usize,                // byte position after current character
+    line_start: usize,              // byte position of current line
+    skip: usize,                    // count of next characters that are already collected
+    temp: String,                   // temporary string to keep collected token
+    escape: Option<String>,         // escape sequence being collected, without backslash
+    string_length: usize,           // length of string being collected in source
+    groups: Vec<Group>,             // open braces, innermost is the last
+    pub output: Vec<Node>,          // parser output
+    pub diagnostics: Vec<JelError>, // every syntax error, ordered by position
+    pub comments: Vec<Comment>,     // comments in written order
+    state: ParserState,             // parser state (normal or string collecting)
 }
 
 // implement default for parser
@@ -65,11 +73,23 @@ impl Default for Parser<'_> {
     fn default() -> Self {
         Self {
             source: "",
+            position: 0,
+            line_start: 0,
+            skip: 0,
             temp: String::new(),
+            escape: None,
+            string_length: 0,
+            groups: Vec::new(),
             output: Vec::new(),
+            diagnostics: Vec::new(),
+            comments: Vec::new(),
             state: ParserState::Token,
             line: 1,
             column: 0,
+            file: None,
+            temp_start: (1, 1),
+            escape_start: (1, 1),
+            comment_own_line: false,
         }
     }
 }
@@ -83,29 +103,151 @@ impl<'a> Parser<'a> {
             ..Default::default()
         }
     }
+
+    // create new parser that marks spans with given file name
+    pub fn with_file(source: &'a str, file: &str) -> Self {
+        Parser {
+            source,
+            file: Some(Rc::from(file)),
+            ..Default::default()
+        }
+    }
+
+    // create a span that starts from given position
+    fn span(&self, (line, column): (usize, usize), length: usize) -> Span {
+        Span {
+            file: self.file.clone(),
+            line,
+            column,
+            length,
+        }
+    }
+
+    // move collected comment
+    fn push_comment(&mut self) {
+        let text = take(&mut self.temp);
+        let span = self.span(self.temp_start, text.chars().count() + 1);
+
+        self.comments.push(Comment {
+            text: text.trim_end().to_string(),
+            own_line: self.comment_own_line,
+            span,
+        });
+    }
+
+    // save a syntax error, parsing continues after it
+    fn report<S: Into<String>>(&mut self, message: S, span: Span) {
+        self.diagnostics
+            .push(JelError::new(ErrorKind::Syntax, message).at(span));
+    }
+
+    // move string argument (if exists)
+    fn push_temp(&mut self) {
+        if !self.temp.is_empty() {
+            let span = self.span(self.temp_start, self.temp.chars().count());
+            let value = take(&mut self.temp);
+            self.push_node(Node::Literal(Rc::new(Literal {
+                value,
+                quoted: false,
+                span,
+            })));
+        }
+    }
+
+    // move string argument, even if it is empty
+    fn push_string(&mut self) {
+        // span covers quotes and escapes as they are written
+        let span = self.span(self.temp_start, self.string_length);
+        let value = take(&mut self.temp);
+        self.state = ParserState::Token;
+        self.push_node(Node::Literal(Rc::new(Literal {
+            value,
+            quoted: true,
+            span,
+        })));
+    }
+
+    // add node to innermost open command, words outside of commands are reported
+    fn push_node(&mut self, node: Node) {
+        match (self.groups.last_mut(), node) {
+            (Some(group), node) => group.arguments.push(node),
+            (None, Node::Literal(literal)) => self.report(
+                format!(
+                    "\"{}\" is outside of a command, it does nothing.",
+                    literal.value
+                ),
+                literal.span.clone(),
+            ),
+            (None, node) => self.output.push(node),
+        }
+    }
 }
 
 // main part of the parser combined with lexer
 impl Parser<'_> {
-    // parse source and append tokens to "output" value
-    pub fn parse(&mut self) {
+    // parse source and append nodes to "output" value
+    // every syntax error is saved to "diagnostics", first one is returned
+    pub fn parse(&mut self) -> Result<(), JelError> {
         // iterate over characters
-        for character in self.source.chars() {
+        for (index, character) in self.source.char_indices() {
+            self.position = index + character.len_utf8();
             self.collect(character);
         }
 
-        // clear garbage data
-        self.clear_garbage();
+        // last line can end with a comment
+        if let ParserState::Comment = self.state {
+            self.push_comment();
+        }
+
+        // strings must be closed before end of the source
+        if let ParserState::String { .. } = self.state {
+            self.report("string is not closed.", self.span(self.temp_start, 1));
+            self.escape = None;
+            self.push_string();
+        }
+
+        // commands must be closed too, innermost one is the last
+        self.push_temp();
+        while let Some(group) = self.groups.last() {
+            self.report(
+                "jel thinks you forgot to close a brace.",
+                group.span.clone(),
+            );
+            self.close_group(self.span((self.line, self.column + 1), 0));
+        }
+
+        self.diagnostics
+            .sort_by_key(|error| error.span.as_ref().map(|span| (span.line, span.column)));
+
+        match self.diagnostics.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
     }
 
     // parse given character
     fn collect(&mut self, character: char) {
+        self.column += 1;
+
+        // skip characters that are collected with previous one
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+
         // match character
         match self.state {
             ParserState::Token => self.collect_token(character),
-            ParserState::String => self.collect_string(character),
+            ParserState::String { raw, triple } => self.collect_string(character, raw, triple),
             ParserState::Comment => self.collect_comment(character),
         }
+
+        // update column and line
+        if character == '\n' {
+            self.line += 1;
+            self.column = 0;
+            self.line_start = self.position;
+        }
     }
 
     // collect normal token
@@ -116,167 +258,298 @@ impl Parser<'_> {
                 let command_name = self.temp.trim().to_string();
                 self.temp = String::new();
 
-                self.output.push(Token::Command(Command {
-                    name: command_name,
-                    ..Default::default()
-                }));
+                // span covers command name, or the brace for concat
+                let span = if command_name.is_empty() {
+                    self.span((self.line, self.column), 1)
+                } else {
+                    self.span(self.temp_start, command_name.chars().count())
+                };
 
-                // push a placeholder
-                self.output.push(Token::PlaceHolder(0x0));
+                self.groups.push(Group {
+                    name: command_name,
+                    arguments: Vec::new(),
+                    span,
+                });
             }
-            '\r' => return,
-            '\n' => {
-                // move string argument (if exists)
-                if !self.temp.is_empty() {
-                    self.output.push(Token::String(take(&mut self.temp)));
-                }
+            '\r' => (),
+            '\n' | ' ' => self.push_temp(),
+            '"' => {
+                // "r" right before quotes makes a raw string
+                let raw = self.temp == "r";
+                self.string_length = 1;
 
-                // update column and line
-                self.line += 1;
-                self.column = 0;
-            }
-            ' ' => {
-                // move string argument (if exists)
-                if !self.temp.is_empty() {
-                    self.output.push(Token::String(take(&mut self.temp)));
+                if raw {
+                    self.temp.clear();
+                    self.string_length += 1;
+                } else {
+                    self.push_temp();
+                    self.temp_start = (self.line, self.column);
                 }
-            }
-            '"' => {
-                // move string argument (if exists)
-                if !self.temp.is_empty() {
-                    self.output.push(Token::String(take(&mut self.temp)));
+
+                // three quotes start a multi-line string
+                let triple = self.source[self.position..].starts_with("\"\"");
+                if triple {
+                    self.skip = 2;
+                    self.string_length += 2;
                 }
 
                 // change state to string collecting
-                self.state = ParserState::String
+                self.state = ParserState::String { raw, triple }
             }
             '#' => {
-                // move string argument (if exists)
-                if !self.temp.is_empty() {
-                    self.output.push(Token::String(take(&mut self.temp)));
-                }
+                self.push_temp();
+
+                // comment is on its own line if there is nothing before it
+                let before = &self.source[self.line_start..self.position - 1];
+                self.comment_own_line = before.trim().is_empty();
+                self.temp_start = (self.line, self.column);
 
                 // change state to comment collecting
                 self.state = ParserState::Comment
             }
             ')' => {
-                // move string argument (if exists)
-                if !self.temp.is_empty() {
-                    self.output.push(Token::String(take(&mut self.temp)));
-                }
-
-                // get argument until we find the closest command
-                let mut args: Vec<Token> = Vec::new();
-
-                // get all of the arguments
-                loop {
-                    if self.output.is_empty() {
-                        debug::send(
-                            self.line,
-                            self.column,
-                            "jel thinks you forgot to open a brace.",
-                        );
-                    } else if let Some(Token::PlaceHolder(0x0)) = self.output.last() {
-                        self.output.pop();
-                        break;
-                    }
-
-                    args.push(self.output.pop().unwrap());
-                }
-
-                // reverse arguments
-                args.reverse();
+                self.push_temp();
 
-                // set arguments
-                if let Some(Token::Command(command)) = self.output.last_mut() {
-                    command.arguments = args;
-                } else {
-                    debug::send(
-                        self.line,
-                        self.column,
-                        "jel thinks you have a syntax error that he can't even solve.",
+                // brace is ignored if there is no command to close
+                if self.groups.is_empty() {
+                    self.report(
+                        "jel thinks you forgot to open a brace.",
+                        self.span((self.line, self.column), 1),
                     );
+                } else {
+                    self.close_group(self.span((self.line, self.column), 1));
                 }
             }
             _ => {
+                // remember where token starts
+                if self.temp.is_empty() {
+                    self.temp_start = (self.line, self.column);
+                }
+
                 // push character to temp value
                 self.temp.push(character)
             }
         }
+    }
 
-        self.column += 1;
+    // close innermost open command and add it to its parent
+    fn close_group(&mut self, end: Span) {
+        let group = match self.groups.pop() {
+            Some(group) => group,
+            None => return,
+        };
+
+        // braces without a name are parameters in some places, otherwise they concat
+        let node = if !group.name.is_empty() {
+            Node::Call(Rc::new(Call {
+                name: group.name,
+                arguments: group.arguments,
+                span: group.span,
+                end,
+            }))
+        } else if self.expects_parameters() {
+            Node::ParamList(Rc::new(self.parameter_list(group, end)))
+        } else {
+            Node::Concat(Rc::new(Concat {
+                arguments: group.arguments,
+                span: group.span,
+                end,
+            }))
+        };
+
+        self.push_node(node);
     }
 
-    // collect string
-    fn collect_string(&mut self, character: char) {
-        // check string close
-        if character == '"' {
-            // check if has an escape
-            if !self.temp.is_empty() {
-                let last_character = self.temp.chars().last().unwrap();
-
-                if last_character != '\\' {
-                    self.state = ParserState::Token;
-                    self.output.push(Token::String(take(&mut self.temp)));
+    // check if next argument of innermost command is a parameter list
+    fn expects_parameters(&self) -> bool {
+        match self.groups.last() {
+            Some(group) => matches!(
+                (group.name.as_str(), group.arguments.len()),
+                ("function", 1) | ("lambda", 0)
+            ),
+            None => false,
+        }
+    }
+
+    // read "(first (second default) ...rest)" pattern
+    fn parameter_list(&mut self, group: Group, end: Span) -> ParamList {
+        let mut parameters = Vec::new();
+
+        for argument in group.arguments {
+            let parameter = match argument {
+                Node::Literal(mut literal) => match literal.value.strip_prefix("...") {
+                    Some(name) => {
+                        let name = name.to_string();
+                        Rc::make_mut(&mut literal).value = name;
+                        Param::Rest(literal)
+                    }
+                    None => Param::Name(literal),
+                },
+                Node::Concat(concat) if concat.arguments.len() == 2 => {
+                    let mut arguments = concat.arguments.iter().cloned();
+
+                    match (arguments.next(), arguments.next()) {
+                        (Some(Node::Literal(name)), Some(value)) => Param::Default(name, value),
+                        _ => {
+                            self.report(
+                                "default parameter must start with a name, like (name value).",
+                                concat.span.clone(),
+                            );
+                            continue;
+                        }
+                    }
                 }
-            } else {
-                self.state = ParserState::Token;
-                self.output.push(Token::String(take(&mut self.temp)));
-            }
-        } else if character == 'n' {
-            // check if is a new line
-            if !self.temp.is_empty() {
-                let last_character = self.temp.chars().last().unwrap();
-
-                if last_character == '\\' {
-                    self.temp.pop();
-                    self.temp.push('\n');
-                    return;
+                node => {
+                    self.report(
+                        "parameters can be a name, (name value) or ...name.",
+                        node.span().clone(),
+                    );
+                    continue;
                 }
-            }
+            };
 
-            // push character
-            self.temp.push(character);
-        } else if character == '0' {
-            // check if is a new line
-            if !self.temp.is_empty() {
-                let last_character = self.temp.chars().last().unwrap();
-
-                if last_character == '\\' {
-                    self.temp.pop();
-                    self.temp.push('\0');
-                    return;
-                }
+            parameters.push(parameter);
+        }
+
+        ParamList {
+            parameters,
+            span: group.span,
+            end,
+        }
+    }
+
+    // collect string
+    fn collect_string(&mut self, character: char, raw: bool, triple: bool) {
+        self.string_length += 1;
+
+        // continue escape sequence
+        if let Some(escape) = self.escape.take() {
+            if self.collect_escape(escape, character) {
+                return;
             }
+        }
 
-            // push character
-            self.temp.push(character);
-        } else if character == '\n' {
-            // end string if new line
-            self.line += 1;
-            self.column = 0;
+        match character {
+            '"' if !triple => self.push_string(),
+            '"' if self.source[self.position..].starts_with("\"\"") => {
+                // closing quotes of a multi-line string
+                self.skip = 2;
+                self.string_length += 2;
+                self.push_string();
+            }
+            '\\' if !raw => {
+                self.escape = Some(String::new());
+                self.escape_start = (self.line, self.column);
+            }
+            '\n' if !triple => {
+                // string is closed here, so rest of the source can be parsed
+                self.report(
+                    "string is not closed before end of the line, use \"\"\" for multi-line strings.",
+                    self.span(self.temp_start, 1),
+                );
+                self.push_string();
+            }
+            // keep new lines same on every platform
+            '\r' if self.source[self.position..].starts_with('\n') => (),
+            _ => self.temp.push(character),
+        }
+    }
 
-            self.state = ParserState::Token;
-            self.output.push(Token::String(take(&mut self.temp)));
-        } else {
-            // push character
-            self.temp.push(character);
+    // collect escape sequence, first character after backslash is the escape kind
+    // returns false if character is not a part of the escape
+    fn collect_escape(&mut self, mut escape: String, character: char) -> bool {
+        escape.push(character);
+
+        match decode_escape(&escape) {
+            Ok(Some(decoded)) => self.temp.push(decoded),
+            Ok(None) => self.escape = Some(escape),
+            Err(message) => {
+                // underline escape with its backslash
+                let span = self.span(self.escape_start, escape.chars().count() + 1);
+                self.report(message, span);
+
+                // quotes and new lines that end an escape early are collected again
+                return !matches!(character, '"' | '\n');
+            }
         }
+
+        true
     }
 
     // collect comment
     fn collect_comment(&mut self, character: char) {
         // check new line
-        if character == '\n' {
-            self.line += 1;
-            self.column = 0;
-            self.state = ParserState::Token;
+        match character {
+            '\n' => {
+                self.push_comment();
+                self.state = ParserState::Token;
+            }
+            _ => self.temp.push(character),
         }
     }
+}
+
+// decode escape sequence without its backslash, returns none if it is not complete yet
+fn decode_escape(escape: &str) -> Result<Option<char>, String> {
+    let mut characters = escape.chars();
+    let kind = characters.next().unwrap_or_default();
+    let rest = characters.as_str();
+
+    match kind {
+        'n' => Ok(Some('\n')),
+        't' => Ok(Some('\t')),
+        'r' => Ok(Some('\r')),
+        '0' => Ok(Some('\0')),
+        '\\' => Ok(Some('\\')),
+        '"' => Ok(Some('"')),
+        '\'' => Ok(Some('\'')),
+        'x' => {
+            // two hex digits, like \x41
+            if let Some(invalid) = rest.chars().find(|digit| !digit.is_ascii_hexdigit()) {
+                return Err(format!(
+                    "invalid character {invalid:?} in \\x escape, it takes 2 hex digits."
+                ));
+            } else if rest.len() < 2 {
+                return Ok(None);
+            }
 
-    // clean garbage data
-    fn clear_garbage(&mut self) {
-        self.output
-            .retain(|value| matches!(value, Token::Command(_)));
+            match u8::from_str_radix(rest, 16) {
+                Ok(code) if code <= 0x7F => Ok(Some(code as char)),
+                _ => Err(format!(
+                    "\\x{rest} is out of range, \\x escapes go up to \\x7F. use \\u{{{rest}}} instead."
+                )),
+            }
+        }
+        'u' => {
+            // up to 6 hex digits in braces, like \u{1F600}
+            let digits = match rest.strip_prefix('{') {
+                Some(digits) => digits,
+                None if rest.is_empty() => return Ok(None),
+                None => return Err("unicode escapes are written as \\u{...}.".to_string()),
+            };
+
+            match digits.strip_suffix('}') {
+                Some("") => Err("\\u{} escape needs at least 1 hex digit.".to_string()),
+                Some(digits) => u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(Some)
+                    .ok_or_else(|| format!("\\u{{{digits}}} is not a valid unicode character.")),
+                None => {
+                    if let Some(invalid) = digits.chars().find(|digit| !digit.is_ascii_hexdigit()) {
+                        Err(format!("invalid character {invalid:?} in \\u escape."))
+                    } else if digits.len() > 6 {
+                        Err("\\u escape takes at most 6 hex digits.".to_string())
+                    } else {
+                        Ok(None)
+                    }
+                }
+            }
+        }
+        '\r' | '\n' => Err("backslash at end of the line is not an escape.".to_string()),
+        _ => Err(format!(
+            "unknown escape \"\\{}\" in string.",
+            kind.escape_default()
+        )),
     }
 }
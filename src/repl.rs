@@ -0,0 +1,230 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jel::debug;
+use jel::machine::Backend;
+use jel::parser;
+use jel::Interpreter;
+use std::env;
+use std::fs::{read_to_string, write, OpenOptions};
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
+
+// history file keeps this many of the last inputs
+const HISTORY_LIMIT: usize = 1000;
+
+// repl struct
+pub struct Repl {
+    interpreter: Interpreter,      // keeps state between inputs
+    history: Vec<String>,          // previous inputs, also used as sources for errors
+    history_path: Option<PathBuf>, // file to save inputs, none if there is no home directory
+}
+
+// implement helper functions
+impl Repl {
+    // create new repl and load history
+    pub fn new(backend: Backend) -> Self {
+        let history_path = history_path();
+        let history = history_path
+            .as_ref()
+            .and_then(|path| read_to_string(path).ok())
+            .map(|data| data.split('\0').map(String::from).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        Self {
+            interpreter: Interpreter::with_backend(backend),
+            history: history.into_iter().filter(|x| !x.is_empty()).collect(),
+            history_path,
+        }
+    }
+
+    // save input to history
+    fn remember(&mut self, input: &str) {
+        self.history.push(input.to_string());
+
+        let path = match &self.history_path {
+            Some(path) => path,
+            None => return,
+        };
+
+        // rewrite history file with the last inputs if it is full
+        if self.history.len() > HISTORY_LIMIT {
+            let last = &self.history[self.history.len() - HISTORY_LIMIT..];
+            let data: String = last.iter().map(|entry| format!("{entry}\0")).collect();
+            let _ = write(path, data);
+            return;
+        }
+
+        // append to history file, entries are separated with nul
+        let file = OpenOptions::new().create(true).append(true).open(path);
+        if let Ok(mut file) = file {
+            let _ = write!(file, "{input}\0");
+        }
+    }
+}
+
+// main part of the repl
+impl Repl {
+    // run repl until end of the input
+    pub fn run(&mut self) {
+        println!("Jel REPL, type :help for help.");
+
+        while let Some(input) = read_input() {
+            match input.trim() {
+                "" => continue,
+                ":exit" | ":quit" => break,
+                ":help" => {
+                    println!(":history    show previous inputs");
+                    println!(":exit       exit from repl");
+                }
+                ":history" => {
+                    for (index, entry) in self.history.iter().enumerate() {
+                        println!("{:>4}  {}", index + 1, entry.replace('\n', "\n      "));
+                    }
+                }
+                _ => {
+                    self.remember(&input);
+                    self.eval(&input);
+                }
+            }
+        }
+    }
+
+    // run given input and print every result
+    fn eval(&mut self, input: &str) {
+        let file = format!("<repl:{}>", self.history.len() - 1);
+        let mut parser = parser::Parser::with_file(input, &file);
+
+        // print every syntax error, nothing is run if there is one
+        if parser.parse().is_err() {
+            for err in &parser.diagnostics {
+                debug::send(err, Some(input));
+            }
+
+            return;
+        }
+
+        // stop at first runtime error
+        for node in &parser.output {
+            let result = self
+                .interpreter
+                .machine()
+                .run_nodes(std::slice::from_ref(node));
+
+            match result {
+                Ok(value) => println!("=> {value}"),
+                Err(err) => {
+                    debug::send(&err, self.source_of(&err));
+                    return;
+                }
+            }
+        }
+    }
+
+    // find input that error happened
+    fn source_of(&self, err: &jel::JelError) -> Option<&str> {
+        let file = err.span.as_ref()?.file.as_deref()?;
+        let index: usize = file
+            .strip_prefix("<repl:")?
+            .strip_suffix('>')?
+            .parse()
+            .ok()?;
+
+        self.history.get(index).map(String::as_str)
+    }
+}
+
+// read an input, continue reading until it is complete
+fn read_input() -> Option<String> {
+    let mut input = String::new();
+
+    loop {
+        print!("{}", if input.is_empty() { ">>> " } else { "... " });
+        let _ = stdout().flush();
+
+        // end of the input
+        let mut line = String::new();
+        match stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                println!();
+                return None;
+            }
+            Ok(_) => input.push_str(&line),
+        }
+
+        if is_complete(&input) {
+            return Some(input.trim_end().to_string());
+        }
+    }
+}
+
+// check if braces are balanced and multi-line strings are closed, ignoring comments
+fn is_complete(source: &str) -> bool {
+    let characters: Vec<char> = source.chars().collect();
+    let mut depth = 0;
+    let mut index = 0;
+
+    while index < characters.len() {
+        match characters[index] {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '#' => {
+                // comments end at new line
+                while index < characters.len() && characters[index] != '\n' {
+                    index += 1;
+                }
+            }
+            '"' => {
+                // same rules as parser: "r" right before quotes makes a raw string
+                let raw = match characters[..index] {
+                    [.., previous, 'r'] => matches!(previous, ' ' | '\n' | '(' | ')' | '"'),
+                    ['r'] => true,
+                    _ => false,
+                };
+                let triple = characters[index..].starts_with(&['"'; 3]);
+                index += if triple { 3 } else { 1 };
+
+                // skip string, only multi-line strings continue after new line
+                loop {
+                    match characters.get(index) {
+                        None => return !triple,
+                        Some('\\') if !raw => index += 1,
+                        Some('"' | '\n') if !triple => break,
+                        Some('"') if characters[index..].starts_with(&['"'; 3]) => {
+                            index += 2;
+                            break;
+                        }
+                        _ => (),
+                    }
+
+                    index += 1;
+                }
+            }
+            _ => (),
+        }
+
+        index += 1;
+    }
+
+    depth <= 0
+}
+
+// history file is kept in home directory
+fn history_path() -> Option<PathBuf> {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    let mut path = PathBuf::from(home);
+    path.push(".jel_history");
+
+    Some(path)
+}
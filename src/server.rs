@@ -0,0 +1,519 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::ast::{Call, Node, Param};
+use crate::checker::check_source;
+use crate::commands::builtin::BUILTINS;
+use crate::commands::function::parse_parameters;
+use crate::debug::{ErrorKind, JelError};
+use crate::machine::SPECIAL_FORMS;
+use crate::parser::{Parser, Span};
+use serde_json::{json, Value as Json};
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+// json-rpc error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+
+// completion item kinds
+const FUNCTION_KIND: i64 = 3;
+const KEYWORD_KIND: i64 = 14;
+
+// function defined with "function" command
+#[derive(Debug, Clone)]
+struct Definition {
+    name: String,  // function name
+    usage: String, // "function(name (parameters))"
+    uri: String,   // document that defines it
+    span: Span,    // where name is written
+}
+
+// language server, it gets json-rpc messages and returns messages to send back
+#[derive(Debug, Default)]
+pub struct Server {
+    documents: HashMap<String, String>, // open documents by uri
+    shutdown: bool,                     // true after "shutdown" request
+    exit_code: Option<i32>,             // set by "exit" notification
+}
+
+// server functions
+impl Server {
+    // create server without open documents
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // exit code, if client asked server to exit
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    // handle a request or notification, returns responses and notifications for client
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let mut output = Vec::new();
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                },
+                "serverInfo": { "name": "jel", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "exit" => {
+                self.exit_code = Some(i32::from(!self.shutdown));
+                Ok(Json::Null)
+            }
+            // documents are synced as a whole
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+                output.push(self.diagnostics(uri));
+                Ok(Json::Null)
+            }
+            "textDocument/didChange" => {
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                }
+
+                output.push(self.diagnostics(uri));
+                Ok(Json::Null)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                output.push(self.diagnostics(uri));
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => Ok(self.hover(uri, &params["position"])),
+            "textDocument/definition" => Ok(self.definition(uri, &params["position"])),
+            "textDocument/completion" => Ok(self.completion(uri)),
+            _ => Err((
+                METHOD_NOT_FOUND,
+                format!("method \"{method}\" is not supported."),
+            )),
+        };
+
+        // notifications don't get a response
+        if let Some(id) = message.get("id") {
+            output.push(match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => error_response(id.clone(), code, &message),
+            });
+        }
+
+        output
+    }
+
+    // problems of a document, closed documents have none
+    fn diagnostics(&self, uri: &str) -> Json {
+        let diagnostics: Vec<Json> = match self.documents.get(uri) {
+            Some(source) => {
+                let path = uri_to_path(uri);
+                check_source(source, &path.to_string_lossy())
+                    .iter()
+                    .map(|err| diagnostic(err, source))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        })
+    }
+
+    // documentation of the command at given position
+    fn hover(&self, uri: &str, position: &Json) -> Json {
+        let source = match self.documents.get(uri) {
+            Some(source) => source,
+            None => return Json::Null,
+        };
+
+        let nodes = parse(source);
+        let call = match location(source, position).and_then(|at| find(&nodes, None, at)) {
+            Some((Node::Call(call), _)) => call,
+            _ => return Json::Null,
+        };
+
+        let (usage, description) = match documentation(&call.name) {
+            Some(documentation) => documentation,
+            None => match self
+                .definitions(uri)
+                .into_iter()
+                .find(|definition| definition.name == call.name)
+            {
+                Some(definition) => {
+                    let path = uri_to_path(&definition.uri);
+                    let file = path.file_name().unwrap_or_default().to_string_lossy();
+                    let line = definition.span.line;
+                    (
+                        definition.usage,
+                        format!("defined at line {line} of {file}."),
+                    )
+                }
+                None => return Json::Null,
+            },
+        };
+
+        json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("```julia\n{usage}\n```\n{description}"),
+            },
+            "range": range(source, &call.span),
+        })
+    }
+
+    // location of the function or imported file at given position
+    fn definition(&self, uri: &str, position: &Json) -> Json {
+        let source = match self.documents.get(uri) {
+            Some(source) => source,
+            None => return Json::Null,
+        };
+
+        let nodes = parse(source);
+        let name = match location(source, position).and_then(|at| find(&nodes, None, at)) {
+            Some((Node::Call(call), _)) => call.name.clone(),
+            // name of "call(name ...)" is a function too
+            Some((Node::Literal(literal), Some(parent))) if parent.name == "call" => {
+                literal.value.clone()
+            }
+            // path of "import(path)" is opened
+            Some((Node::Literal(literal), Some(parent))) if parent.name == "import" => {
+                let path = import_path(uri, &literal.value);
+                if !path.is_file() {
+                    return Json::Null;
+                }
+
+                let start = json!({ "line": 0, "character": 0 });
+                return json!({
+                    "uri": path_to_uri(&path),
+                    "range": { "start": start, "end": start },
+                });
+            }
+            _ => return Json::Null,
+        };
+
+        match self
+            .definitions(uri)
+            .into_iter()
+            .find(|definition| definition.name == name)
+        {
+            Some(definition) => {
+                // imported documents may not be open
+                let defined_in = match self.documents.get(&definition.uri) {
+                    Some(source) => source.clone(),
+                    None => read_to_string(uri_to_path(&definition.uri)).unwrap_or_default(),
+                };
+
+                json!({
+                    "uri": definition.uri,
+                    "range": range(&defined_in, &definition.span),
+                })
+            }
+            None => Json::Null,
+        }
+    }
+
+    // names of commands and functions
+    fn completion(&self, uri: &str) -> Json {
+        let mut items = Vec::new();
+
+        for (name, builtin) in BUILTINS {
+            let (usage, description) = builtin.doc();
+            items.push(json!({
+                "label": name,
+                "kind": FUNCTION_KIND,
+                "detail": usage,
+                "documentation": description,
+            }));
+        }
+
+        for form in SPECIAL_FORMS {
+            items.push(json!({
+                "label": form.name,
+                "kind": KEYWORD_KIND,
+                "detail": form.usage,
+                "documentation": form.description,
+            }));
+        }
+
+        // functions defined more than once are listed once
+        let mut seen = HashSet::new();
+        for definition in self.definitions(uri) {
+            if seen.insert(definition.name.clone()) {
+                items.push(json!({
+                    "label": definition.name,
+                    "kind": FUNCTION_KIND,
+                    "detail": definition.usage,
+                }));
+            }
+        }
+
+        Json::Array(items)
+    }
+
+    // functions defined in a document and in files it imports
+    fn definitions(&self, uri: &str) -> Vec<Definition> {
+        let mut found = Vec::new();
+
+        if let Some(source) = self.documents.get(uri) {
+            let mut visited = HashSet::from([uri_to_path(uri)]);
+            define(&parse(source), uri, &mut visited, &mut found);
+        }
+
+        found
+    }
+}
+
+// create an error response
+pub fn error_response(id: Json, code: i64, message: &str) -> Json {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+// parse a document, commands before and after syntax errors are kept
+fn parse(source: &str) -> Vec<Node> {
+    let mut parser = Parser::new(source);
+    let _ = parser.parse();
+    parser.output
+}
+
+// find functions defined in nodes, imported files are read from disk
+fn define(nodes: &[Node], uri: &str, visited: &mut HashSet<PathBuf>, found: &mut Vec<Definition>) {
+    for node in nodes {
+        let arguments = match node {
+            Node::Call(call) => &call.arguments,
+            Node::Concat(concat) => &concat.arguments,
+            Node::ParamList(_) | Node::Literal(_) => continue,
+        };
+
+        match (node, arguments.as_slice()) {
+            (Node::Call(call), [Node::Literal(name), parameters, _]) if call.name == "function" => {
+                let parameters: Vec<String> = parse_parameters(parameters)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|parameter| parameter.to_string())
+                    .collect();
+
+                found.push(Definition {
+                    name: name.value.clone(),
+                    usage: format!("function({} ({}))", name.value, parameters.join(" ")),
+                    uri: uri.to_string(),
+                    span: name.span.clone(),
+                });
+            }
+            (Node::Call(call), [.., Node::Literal(path)]) if call.name == "import" => {
+                let path = import_path(uri, &path.value);
+
+                if visited.insert(path.clone()) {
+                    if let Ok(source) = read_to_string(&path) {
+                        define(&parse(&source), &path_to_uri(&path), visited, found);
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        define(arguments, uri, visited, found);
+    }
+}
+
+// usage and description of a builtin or special form
+fn documentation(name: &str) -> Option<(String, String)> {
+    if let Some((_, builtin)) = BUILTINS.iter().find(|(builtin, _)| *builtin == name) {
+        let (usage, description) = builtin.doc();
+        return Some((usage.to_string(), description.to_string()));
+    }
+
+    SPECIAL_FORMS
+        .iter()
+        .find(|form| form.name == name)
+        .map(|form| (form.usage.to_string(), form.description.to_string()))
+}
+
+// find command name or literal at given position, with the command it is written in
+fn find<'a>(
+    nodes: &'a [Node],
+    parent: Option<&'a Call>,
+    at: (usize, usize),
+) -> Option<(&'a Node, Option<&'a Call>)> {
+    for node in nodes {
+        if contains(node.span(), at) && matches!(node, Node::Call(_) | Node::Literal(_)) {
+            return Some((node, parent));
+        }
+
+        let found = match node {
+            Node::Call(call) => find(&call.arguments, Some(call), at),
+            Node::Concat(concat) => find(&concat.arguments, parent, at),
+            Node::ParamList(list) => list
+                .parameters
+                .iter()
+                .find_map(|parameter| match parameter {
+                    Param::Default(_, value) => find(std::slice::from_ref(value), parent, at),
+                    Param::Name(_) | Param::Rest(_) => None,
+                }),
+            Node::Literal(_) => None,
+        };
+
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    None
+}
+
+// check if a span contains given line and column, end of the span is included
+fn contains(span: &Span, (line, column): (usize, usize)) -> bool {
+    span.line == line && column >= span.column && column <= span.column + span.length
+}
+
+// convert an error to a diagnostic, syntax errors stop the script and others are warnings
+fn diagnostic(err: &JelError, source: &str) -> Json {
+    let span = err.span.clone().unwrap_or_default();
+    let severity = if err.kind == ErrorKind::Syntax { 1 } else { 2 };
+
+    json!({
+        "range": range(source, &span),
+        "severity": severity,
+        "code": err.kind.to_string(),
+        "source": "jel",
+        "message": err.message,
+    })
+}
+
+// convert a span to a range, clients count columns in utf-16
+fn range(source: &str, span: &Span) -> Json {
+    json!({
+        "start": position(source, span.line, span.column),
+        "end": position(source, span.line, span.column + span.length),
+    })
+}
+
+// convert a line and column to a zero-based position
+fn position(source: &str, line: usize, column: usize) -> Json {
+    let text = source
+        .lines()
+        .nth(line.saturating_sub(1))
+        .unwrap_or_default();
+    let character: usize = text
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(char::len_utf16)
+        .sum();
+
+    json!({ "line": line.saturating_sub(1), "character": character })
+}
+
+// convert a zero-based position to line and column
+fn location(source: &str, position: &Json) -> Option<(usize, usize)> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+    let text = source.lines().nth(line).unwrap_or_default();
+
+    let mut units = 0;
+    let mut column = 1;
+    for character_at in text.chars() {
+        if units >= character {
+            break;
+        }
+
+        units += character_at.len_utf16();
+        column += 1;
+    }
+
+    Some((line + 1, column))
+}
+
+// path of an imported file, imports are relative to the importing file
+fn import_path(uri: &str, path: &str) -> PathBuf {
+    let importer = uri_to_path(uri);
+    importer.parent().unwrap_or(Path::new("")).join(path)
+}
+
+// convert a "file://" uri to a path
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    // decode "%20" like escapes
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, next)) = rest.split_first() {
+        let escaped = next
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (byte, escaped) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &next[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = next;
+            }
+        }
+    }
+
+    let path = String::from_utf8_lossy(&bytes).to_string();
+
+    // windows paths are written like "/C:/jel"
+    match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => PathBuf::from(&path[1..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+// convert a path to a "file://" uri
+fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from(if path.starts_with('/') {
+        "file://"
+    } else {
+        "file:///"
+    });
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    uri
+}
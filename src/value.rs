@@ -0,0 +1,271 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::commands::function::FunctionData;
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+// runtime value enum
+#[derive(Debug, Clone, Default)]
+pub enum Value {
+    #[default]
+    Nil, // for nil
+    Bool(bool),                   // for booleans
+    Int(i64),                     // for integers
+    BigInt(BigInt),               // for integers that don't fit in i64
+    Float(f64),                   // for floats
+    Number(String),               // for number literals that are written differently, like 007
+    Str(String),                  // for strings
+    List(Vec<Value>),             // for lists
+    Map(BTreeMap<String, Value>), // for maps
+    Function(Rc<FunctionData>),   // for lambdas
+}
+
+// value functions
+impl Value {
+    // convert a literal to value, quoted literals are always strings
+    pub fn from_literal(text: &str, quoted: bool) -> Self {
+        if quoted {
+            return Value::Str(text.to_string());
+        }
+
+        match text {
+            "nil" => Value::Nil,
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => match Value::parse_number(text) {
+                // text is kept if number is printed differently, it is parsed when needed
+                Some(number) if number.to_string() != text => Value::Number(text.to_string()),
+                Some(number) => number,
+                None => Value::Str(text.to_string()),
+            },
+        }
+    }
+
+    // parse a number, words like "inf" or "nan" are not numbers
+    pub fn parse_number(text: &str) -> Option<Self> {
+        if let Ok(value) = text.parse::<i64>() {
+            Some(Value::Int(value))
+        } else if let Ok(value) = text.parse::<BigInt>() {
+            Some(Value::BigInt(value))
+        } else if text.chars().any(|character| character.is_ascii_digit()) {
+            text.parse::<f64>().ok().map(Value::Float)
+        } else {
+            None
+        }
+    }
+
+    // return type name of the value
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Int(_) | Value::BigInt(_) => "int",
+            Value::Float(_) => "float",
+            Value::Number(_) => self
+                .to_number()
+                .map_or("string", |number| number.type_name()),
+            Value::Str(_) => "string",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Function(_) => "function",
+        }
+    }
+
+    // convert value to a number, strings are parsed
+    pub fn to_number(&self) -> Option<Self> {
+        match self {
+            Value::Int(_) | Value::BigInt(_) | Value::Float(_) => Some(self.clone()),
+            Value::Str(text) | Value::Number(text) => Value::parse_number(text.trim()),
+            _ => None,
+        }
+    }
+
+    // convert value to a big integer, if it is an integer
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self.to_number()? {
+            Value::Int(value) => Some(BigInt::from(value)),
+            Value::BigInt(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // create an integer value, big integers are only used if needed
+    pub fn from_bigint(value: BigInt) -> Self {
+        match value.to_i64() {
+            Some(value) => Value::Int(value),
+            None => Value::BigInt(value),
+        }
+    }
+
+    // convert value to a float, if it is a number
+    pub fn to_float(&self) -> Option<f64> {
+        match self.to_number()? {
+            Value::Int(value) => Some(value as f64),
+            Value::BigInt(value) => value.to_f64(),
+            Value::Float(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // check if value is true for conditions
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Bool(value) => *value,
+            Value::Int(value) => *value != 0,
+            Value::BigInt(value) => !value.is_zero(),
+            Value::Float(value) => *value != 0.0,
+            // strings keep old behaviour, where every value was a string
+            Value::Str(value) | Value::Number(value) => {
+                !matches!(value.as_str(), "" | "false" | "0" | "0.0" | "nil")
+            }
+            Value::List(items) => !items.is_empty(),
+            Value::Map(pairs) => !pairs.is_empty(),
+            Value::Function(_) => true,
+        }
+    }
+
+    // compare values, numbers are compared by value and mixed types by text
+    pub fn loose_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            // number literals written differently are compared by value with numbers
+            (
+                Value::Number(_),
+                Value::Int(_) | Value::BigInt(_) | Value::Float(_) | Value::Number(_),
+            ) => self.to_number().unwrap().loose_eq(other),
+            (Value::Int(_) | Value::BigInt(_) | Value::Float(_), Value::Number(_)) => {
+                self.loose_eq(&other.to_number().unwrap())
+            }
+            (Value::Int(first), Value::Int(second)) => first == second,
+            (Value::Int(_) | Value::BigInt(_), Value::Int(_) | Value::BigInt(_)) => {
+                self.to_bigint() == other.to_bigint()
+            }
+            (
+                Value::Int(_) | Value::BigInt(_) | Value::Float(_),
+                Value::Int(_) | Value::BigInt(_) | Value::Float(_),
+            ) => self.to_float() == other.to_float(),
+            (Value::List(first), Value::List(second)) => {
+                first.len() == second.len()
+                    && first
+                        .iter()
+                        .zip(second)
+                        .all(|(first, second)| first.loose_eq(second))
+            }
+            (Value::Map(first), Value::Map(second)) => {
+                first.len() == second.len()
+                    && first
+                        .iter()
+                        .zip(second)
+                        .all(|(first, second)| first.0 == second.0 && first.1.loose_eq(second.1))
+            }
+            // functions are only equal to themselves
+            (Value::Function(first), Value::Function(second)) => Rc::ptr_eq(first, second),
+            (Value::Function(_), _) | (_, Value::Function(_)) => false,
+            _ => self.to_string() == other.to_string(),
+        }
+    }
+}
+
+// implement display for value
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Int(value) => write!(f, "{value}"),
+            Value::BigInt(value) => write!(f, "{value}"),
+            // floats always have a fractional part, like "5.0"
+            Value::Float(value) if value.fract() == 0.0 => write!(f, "{value}.0"),
+            Value::Float(value) => write!(f, "{value}"),
+            Value::Str(value) | Value::Number(value) => write!(f, "{value}"),
+            Value::List(items) => {
+                write!(f, "[")?;
+
+                for (index, item) in items.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, " ")?;
+                    }
+
+                    write!(f, "{item}")?;
+                }
+
+                write!(f, "]")
+            }
+            Value::Map(pairs) => {
+                write!(f, "{{")?;
+
+                for (index, (key, value)) in pairs.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, " ")?;
+                    }
+
+                    write!(f, "{key}: {value}")?;
+                }
+
+                write!(f, "}}")
+            }
+            Value::Function(function) => {
+                let arguments: Vec<String> = function
+                    .body
+                    .parameters
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .collect();
+
+                write!(f, "<function ({})>", arguments.join(" "))
+            }
+        }
+    }
+}
+
+// implement conversions for common types
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(value: BigInt) -> Self {
+        Value::from_bigint(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
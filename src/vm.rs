@@ -0,0 +1,240 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::commands::error::error_to_value;
+use crate::commands::function::check_argument_count;
+use crate::commands::iterate::{Binding, Iteration};
+use crate::compiler::{Chunk, Op};
+use crate::debug::{ErrorKind, JelError};
+use crate::machine;
+use crate::value::Value;
+
+// open loop or try, keeps stack heights to restore when it catches
+#[derive(Debug)]
+enum Handler {
+    Loop {
+        break_to: usize,    // instruction of loop end
+        continue_to: usize, // instruction after loop start
+        stack: usize,       // value stack height
+        iterations: usize,  // iteration stack height
+    },
+    Try {
+        catch: usize,      // instruction of error handler
+        stack: usize,      // value stack height, variable name is on top
+        iterations: usize, // iteration stack height
+    },
+}
+
+// state of a running chunk
+#[derive(Default)]
+struct Frame {
+    stack: Vec<Value>,                     // value stack
+    iterations: Vec<(Iteration, Binding)>, // active "for" loops
+    handlers: Vec<Handler>,                // active loops and tries
+}
+
+impl machine::Machine {
+    // run a chunk and return its output
+    pub fn run_chunk(&self, chunk: &Chunk) -> Result<Value, JelError> {
+        let mut frame = Frame::default();
+        let mut ip = 0;
+
+        while ip < chunk.code.len() {
+            let op = chunk.code[ip];
+            ip += 1;
+
+            if let Err(err) = self.step(chunk, op, &mut ip, &mut frame) {
+                // errors without location will point this instruction
+                ip = self.unwind(err.or_at(&chunk.spans[ip - 1]), &mut frame)?;
+            }
+        }
+
+        Ok(frame.stack.pop().unwrap_or_default())
+    }
+
+    // run an instruction
+    fn step(
+        &self,
+        chunk: &Chunk,
+        op: Op,
+        ip: &mut usize,
+        frame: &mut Frame,
+    ) -> Result<(), JelError> {
+        let stack = &mut frame.stack;
+
+        match op {
+            Op::Constant(index) => stack.push(chunk.constants[index].clone()),
+            Op::Pop => {
+                stack.pop();
+            }
+            Op::Replace => {
+                let value = stack.pop().unwrap();
+                *stack.last_mut().unwrap() = value;
+            }
+            Op::Builtin(builtin, 2) => {
+                let second = stack.pop().unwrap();
+                let first = stack.pop().unwrap();
+                stack.push(self.apply_binary(builtin, first, second)?);
+            }
+            Op::Builtin(builtin, argc) => {
+                let arguments = stack.split_off(stack.len() - argc);
+                stack.push(self.apply_builtin(builtin, arguments)?);
+            }
+            Op::Command(index, argc) => {
+                let arguments = stack.split_off(stack.len() - argc);
+                let command = &chunk.commands[index];
+                stack.push(command.handler.run(self, arguments)?);
+            }
+            Op::Get(name) => stack.push(self.read_variable(&chunk.names[name])?),
+            Op::Move(name) => stack.push(self.move_variable(&chunk.names[name])?),
+            Op::Clone(name) => stack.push(self.clone_variable(&chunk.names[name])?),
+            Op::Let(name) => {
+                let value = stack.pop().unwrap();
+                self.assign_variable(&chunk.names[name], value);
+                stack.push(Value::Nil);
+            }
+            Op::GetSlot(slot) => stack.push(self.read_slot(slot)?),
+            Op::MoveSlot(slot) => stack.push(self.move_slot(slot)?),
+            Op::CloneSlot(slot) => stack.push(self.clone_slot(slot)?),
+            Op::LetSlot(slot) => {
+                let value = stack.pop().unwrap();
+                self.assign_slot(slot, value);
+                stack.push(Value::Nil);
+            }
+            Op::Jump(to) => *ip = to,
+            Op::JumpIfFalse(to) => {
+                if !stack.pop().unwrap().is_truthy() {
+                    *ip = to;
+                }
+            }
+            Op::LoopStart(break_to) => frame.handlers.push(Handler::Loop {
+                break_to,
+                continue_to: *ip,
+                stack: stack.len(),
+                iterations: frame.iterations.len(),
+            }),
+            Op::LoopEnd | Op::TryEnd => {
+                frame.handlers.pop();
+            }
+            Op::IterStart(binding) => {
+                let iterable = stack.pop().unwrap();
+                let name = stack.pop().unwrap().to_string();
+                let pattern = binding.map(|index| &chunk.bindings[index]);
+                let binding = Binding::select(pattern, name, &iterable);
+                let iteration = Iteration::new(iterable, &binding);
+
+                let binding = binding.resolve(&self.variables.borrow());
+                frame.iterations.push((iteration, binding));
+            }
+            Op::IterNext(end) => {
+                let (iteration, binding) = frame.iterations.last_mut().unwrap();
+                if !iteration.bind_next(self, binding) {
+                    *ip = end;
+                }
+            }
+            Op::IterEnd => {
+                frame.iterations.pop();
+            }
+            Op::TryStart(catch) => frame.handlers.push(Handler::Try {
+                catch,
+                stack: stack.len(),
+                iterations: frame.iterations.len(),
+            }),
+            Op::Lookup(name, argc) => {
+                let data = self.lookup_function(&chunk.names[name])?;
+                check_argument_count(&data.name, &data.body.parameters, argc)?;
+                stack.push(Value::Function(data));
+            }
+            Op::Callee(argc) => {
+                let data = self.find_function(stack.pop().unwrap())?;
+                check_argument_count(&data.name, &data.body.parameters, argc)?;
+                stack.push(Value::Function(data));
+            }
+            Op::Invoke(argc) => {
+                let arguments = stack.split_off(stack.len() - argc);
+                let data = match stack.pop() {
+                    Some(Value::Function(data)) => data,
+                    _ => unreachable!("function must be below its arguments"),
+                };
+
+                stack.push(self.invoke(&data, arguments, &chunk.spans[*ip - 1])?);
+            }
+            Op::Function(index) => {
+                let name = stack.pop().unwrap().to_string();
+                self.define_function(name, chunk.bodies[index].clone());
+                stack.push(Value::Nil);
+            }
+            Op::Lambda(index) => stack.push(self.make_lambda(chunk.bodies[index].clone())),
+            Op::Eval(index) => stack.push(self.process(&chunk.nodes[index])?),
+        }
+
+        Ok(())
+    }
+
+    // find the handler of an error and return the instruction to continue from
+    fn unwind(&self, err: JelError, frame: &mut Frame) -> Result<usize, JelError> {
+        // "return" and uncaught errors leave the chunk
+        let position = match err.kind {
+            ErrorKind::Return => None,
+            // control flow passes through tries
+            ErrorKind::Break | ErrorKind::Continue => frame
+                .handlers
+                .iter()
+                .rposition(|handler| matches!(handler, Handler::Loop { .. })),
+            _ => frame
+                .handlers
+                .iter()
+                .rposition(|handler| matches!(handler, Handler::Try { .. })),
+        };
+
+        let position = match position {
+            Some(position) => position,
+            None => return Err(err),
+        };
+
+        // close everything opened after the handler
+        frame.handlers.truncate(position + 1);
+        match frame.handlers[position] {
+            Handler::Loop {
+                break_to,
+                continue_to,
+                stack,
+                iterations,
+            } => {
+                frame.stack.truncate(stack);
+                frame.iterations.truncate(iterations);
+
+                if err.kind == ErrorKind::Break {
+                    Ok(break_to)
+                } else {
+                    Ok(continue_to)
+                }
+            }
+            Handler::Try {
+                catch,
+                stack,
+                iterations,
+            } => {
+                frame.handlers.pop();
+                frame.stack.truncate(stack);
+                frame.iterations.truncate(iterations);
+
+                // bind error to the variable
+                let variable_name = frame.stack.pop().unwrap().to_string();
+                self.set_variable(variable_name, error_to_value(&err));
+                Ok(catch)
+            }
+        }
+    }
+}
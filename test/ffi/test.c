@@ -25,4 +25,8 @@ const char* call_from_c(const char* got_arg) {
 const char* borrow(const char* got_arg) {
     printf("with formatted: \"%s\"\n", got_arg);
     return got_arg;
+}
+
+const char* echo(const char* got_arg) {
+    return got_arg;
 }
\ No newline at end of file
@@ -21,5 +21,6 @@
 
 const char* call_from_c(const char* arg);
 const char* borrow(const char* arg);
+const char* echo(const char* arg);
 
 #endif /* TEST_H */
\ No newline at end of file
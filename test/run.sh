@@ -0,0 +1,91 @@
+#!/bin/sh
+
+# Copyright 2022 aiocat
+# 
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+# 
+#     http://www.apache.org/licenses/LICENSE-2.0
+# 
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+
+# run every test with vm and tree-walker, outputs must be same
+cd "$(dirname "$0")" || exit 1
+cargo build --quiet || exit 1
+jel="$(pwd)/../target/debug/jel"
+failed=0
+
+# ffi test loads its library from its directory
+(cd ffi && cc -shared -fpic -Wall -Werror -o libtest.so test.c) || exit 1
+
+for test in */main.jel; do
+    dir=$(dirname "$test")
+    vm=$(cd "$dir" && "$jel" main.jel 2>&1)
+    tree=$(cd "$dir" && "$jel" --tree main.jel 2>&1)
+
+    if [ "$vm" != "$tree" ]; then
+        echo "[$dir] backends differ:"
+        printf '%s\n' "$vm" > /tmp/jel-vm.txt
+        printf '%s\n' "$tree" > /tmp/jel-tree.txt
+        diff /tmp/jel-tree.txt /tmp/jel-vm.txt
+        failed=1
+    elif [ "$(printf '%s\n' "$vm" | tail -n 1)" != "Success!" ]; then
+        echo "[$dir] failed:"
+        printf '%s\n' "$vm"
+        failed=1
+    else
+        echo "[$dir] ok"
+    fi
+done
+
+# formatting the input of format test must give its main.jel, which is kept as it is
+cp format/input.jel /tmp/jel-format.jel
+"$jel" fmt /tmp/jel-format.jel
+cp format/main.jel /tmp/jel-format-again.jel
+"$jel" fmt /tmp/jel-format-again.jel
+
+if ! diff format/main.jel /tmp/jel-format.jel > /tmp/jel-format.txt \
+    || ! diff format/main.jel /tmp/jel-format-again.jel > /tmp/jel-format.txt; then
+    echo "[format] formatter output differs:"
+    cat /tmp/jel-format.txt
+    failed=1
+else
+    echo "[format] formatter ok"
+fi
+
+# checker must find every mistake of check test, and nothing in its main.jel
+checked=$(cd check && "$jel" check main.jel mistakes.jel 2>&1)
+
+if [ "$checked" != "$(cat check/expected.txt)" ]; then
+    echo "[check] checker output differs:"
+    printf '%s\n' "$checked" > /tmp/jel-check.txt
+    diff check/expected.txt /tmp/jel-check.txt
+    failed=1
+else
+    echo "[check] checker ok"
+fi
+
+# language server must answer messages of lsp test, its directory is written as DIR
+# and headers are removed from answers, because their length depends on directory
+dir="$(pwd)/lsp"
+answered=$(while IFS= read -r message; do
+    message=$(printf "%s" "$message" | sed "s|DIR|$dir|g")
+    printf 'Content-Length: %d\r\n\r\n%s' "${#message}" "$message"
+done < lsp/messages.txt | "$jel" lsp | tr -d '\r' \
+    | sed "s/}Content-Length/}\nContent-Length/g; s|$dir|DIR|g" | grep -v '^Content-Length\|^$')
+
+if [ "$answered" != "$(cat lsp/expected.txt)" ]; then
+    echo "[lsp] language server output differs:"
+    printf '%s\n' "$answered" > /tmp/jel-lsp.txt
+    diff lsp/expected.txt /tmp/jel-lsp.txt
+    failed=1
+else
+    echo "[lsp] language server ok"
+fi
+
+exit $failed
@@ -0,0 +1,151 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use common::each_backend;
+use jel::ast::Node;
+use jel::debug::ErrorKind;
+use jel::machine::Backend;
+use jel::Value;
+use std::rc::Rc;
+
+#[test]
+fn returns_output_of_last_command() {
+    each_backend(|mut interpreter| {
+        let value = interpreter.eval_str("let(x 2) +(get(x) 40)").unwrap();
+        assert!(matches!(value, Value::Int(42)), "got {value:?}");
+
+        let value = interpreter.eval_str("list(a \"b c\")").unwrap();
+        assert_eq!(value.to_string(), "[a b c]");
+        assert!(matches!(interpreter.eval_str("").unwrap(), Value::Nil));
+    });
+}
+
+#[test]
+fn returns_errors_with_kind_and_span() {
+    each_backend(|mut interpreter| {
+        let err = interpreter
+            .eval_str("let(a 1)\n  get(missing)")
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnknownVariable);
+
+        let span = err.span.expect("error must have a location");
+        assert_eq!((span.line, span.column, span.length), (2, 3, 3));
+        assert_eq!(span.file, None);
+
+        // syntax errors are returned before anything runs
+        let err = interpreter.eval_str("print(open").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Syntax);
+        assert_eq!(err.span.map(|span| span.line), Some(1));
+    });
+}
+
+#[test]
+fn errors_point_given_file() {
+    each_backend(|mut interpreter| {
+        let err = interpreter
+            .eval_source("/(1 0)", "billing.jel")
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Arithmetic);
+        assert_eq!(err.span.unwrap().file.as_deref(), Some("billing.jel"));
+    });
+}
+
+#[test]
+fn keeps_state_between_evaluations() {
+    each_backend(|mut interpreter| {
+        interpreter
+            .eval_str("let(total 10) function(double (n) *(get(n) 2))")
+            .unwrap();
+
+        let value = interpreter.eval_str("double(get(total))").unwrap();
+        assert!(matches!(value, Value::Int(20)), "got {value:?}");
+
+        // "get" consumed the variable in previous evaluation
+        let err = interpreter.eval_str("get(total)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnknownVariable);
+    });
+}
+
+#[test]
+fn lambdas_share_body_of_their_command() {
+    each_backend(|mut interpreter| {
+        let value = interpreter
+            .eval_str(
+                "let(made list())
+                for(2 i let(made list.push(get(made) lambda((n) +(get(n) 1)))))
+                get(made)",
+            )
+            .unwrap();
+
+        let functions: Vec<_> = match value {
+            Value::List(items) => items
+                .into_iter()
+                .map(|item| match item {
+                    Value::Function(data) => data,
+                    other => panic!("expected a lambda, got {other:?}"),
+                })
+                .collect(),
+            other => panic!("expected a list, got {other:?}"),
+        };
+
+        // body node is not copied from the syntax tree
+        match (&functions[0].body.value, &functions[1].body.value) {
+            (Node::Call(first), Node::Call(second)) => assert!(Rc::ptr_eq(first, second)),
+            other => panic!("expected calls, got {other:?}"),
+        }
+
+        // vm compiles body once for every lambda made by the command
+        if interpreter.machine().backend.get() == Backend::Vm {
+            assert!(Rc::ptr_eq(&functions[0].body, &functions[1].body));
+        }
+    });
+}
+
+// run a test on a thread with default stack size of spawned threads
+fn on_small_stack(test: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new()
+        .stack_size(2 * 1024 * 1024)
+        .spawn(test)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn endless_recursion_is_a_stack_overflow_error() {
+    on_small_stack(|| {
+        each_backend(|mut interpreter| {
+            let err = interpreter
+                .eval_str("function(forever (n) forever(+(get(n) 1))) forever(0)")
+                .unwrap_err();
+            assert_eq!(err.kind, ErrorKind::StackOverflow);
+            assert_eq!(err.backtrace.len(), jel::machine::DEFAULT_MAX_DEPTH);
+        });
+    });
+}
+
+#[test]
+fn deep_recursion_fits_in_raised_depth() {
+    on_small_stack(|| {
+        each_backend(|mut interpreter| {
+            let source = "pragma(max_depth 20000)
+                function(count (n) if(equals(clone(n) 0) 0 +(1 count(-(get(n) 1)))))
+                count(10000)";
+            let value = interpreter.eval_str(source).unwrap();
+            assert!(matches!(value, Value::Int(10000)), "got {value:?}");
+        });
+    });
+}
@@ -0,0 +1,128 @@
+// Copyright 2022 aiocat
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use common::each_backend;
+use jel::debug::ErrorKind;
+use jel::{Arity, Interpreter, Value};
+
+// register "double" command to the interpreter
+fn register_double(interpreter: &Interpreter) {
+    interpreter
+        .machine()
+        .register(
+            "double",
+            Arity::Exact(1),
+            |_machine, mut arguments| match arguments.pop().unwrap() {
+                Value::Int(number) => Ok(Value::Int(number * 2)),
+                other => Ok(Value::Str(format!("{other}{other}"))),
+            },
+        );
+}
+
+#[test]
+fn registered_command_can_be_called() {
+    each_backend(|mut interpreter| {
+        register_double(&interpreter);
+
+        let value = interpreter.eval_str("double(+(20 1))").unwrap();
+        assert!(matches!(value, Value::Int(42)), "got {value:?}");
+
+        // commands can be used in function bodies
+        let value = interpreter
+            .eval_str("function(quad (n) double(double(get(n)))) quad(ab)")
+            .unwrap();
+        assert_eq!(value.to_string(), "abababab");
+    });
+}
+
+#[test]
+fn arity_is_checked_before_arguments_run() {
+    each_backend(|mut interpreter| {
+        register_double(&interpreter);
+
+        let err = interpreter
+            .eval_str("let(x 1) double(1 get(x))")
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Arity);
+
+        // arguments are not evaluated, so the variable is still there
+        let value = interpreter.eval_str("get(x)").unwrap();
+        assert!(matches!(value, Value::Int(1)), "got {value:?}");
+
+        let err = interpreter.eval_str("double()").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Arity);
+    });
+}
+
+#[test]
+fn registered_command_overrides_builtin() {
+    each_backend(|mut interpreter| {
+        interpreter
+            .machine()
+            .register("not", Arity::Any, |_machine, arguments| {
+                Ok(Value::Int(arguments.len() as i64))
+            });
+
+        let value = interpreter.eval_str("not(true)").unwrap();
+        assert!(matches!(value, Value::Int(1)), "got {value:?}");
+
+        // builtin took one argument, the new command takes any
+        let value = interpreter.eval_str("not(a b c)").unwrap();
+        assert!(matches!(value, Value::Int(3)), "got {value:?}");
+    });
+}
+
+#[test]
+fn unknown_names_are_function_calls() {
+    each_backend(|mut interpreter| {
+        register_double(&interpreter);
+
+        let value = interpreter
+            .eval_str("function(triple (n) *(get(n) 3)) triple(double(2))")
+            .unwrap();
+        assert!(matches!(value, Value::Int(12)), "got {value:?}");
+
+        let err = interpreter.eval_str("missing(1)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnknownFunction);
+    });
+}
+
+#[test]
+fn commands_registered_later_are_used_by_called_functions() {
+    each_backend(|mut interpreter| {
+        interpreter
+            .eval_str("function(check (n) not(get(n))) function(twice (n) double(get(n)))")
+            .unwrap();
+
+        // bodies are compiled on the first call, before commands are registered
+        let value = interpreter.eval_str("check(true)").unwrap();
+        assert!(matches!(value, Value::Bool(false)), "got {value:?}");
+        let err = interpreter.eval_str("twice(4)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnknownFunction);
+
+        register_double(&interpreter);
+        interpreter
+            .machine()
+            .register("not", Arity::Exact(1), |_machine, _arguments| {
+                Ok(Value::Str("overridden".to_string()))
+            });
+
+        let value = interpreter.eval_str("twice(4)").unwrap();
+        assert!(matches!(value, Value::Int(8)), "got {value:?}");
+        let value = interpreter.eval_str("check(true)").unwrap();
+        assert_eq!(value.to_string(), "overridden");
+    });
+}